@@ -0,0 +1,296 @@
+use std::time::Instant;
+
+use cgmath::{InnerSpace, Vector3};
+use microvoxel_raycaster::brickmap::BrickMap;
+use microvoxel_raycaster::distance_field::{DistanceFieldGrid, Metric};
+use microvoxel_raycaster::grid::VoxelGrid;
+use microvoxel_raycaster::heightfield::HeightField;
+use microvoxel_raycaster::interval::Interval;
+use microvoxel_raycaster::mip_pyramid::OccupancyPyramid;
+use microvoxel_raycaster::octree::SparseVoxelOctree;
+use microvoxel_raycaster::ray::Ray;
+use microvoxel_raycaster::traversal::{VoxelHit, VoxelTraversal};
+use rand::Rng;
+use rand_pcg::Pcg64Mcg;
+
+const RAYS_X: u32 = 320;
+const RAYS_Y: u32 = 180;
+const ITERATIONS: u32 = 5;
+const SIZE: [usize; 3] = [256, 64, 256];
+
+/// Nothing at all, every ray crosses the whole grid.
+fn empty(size_x: usize, size_y: usize, size_z: usize) -> VoxelGrid {
+    VoxelGrid::new(size_x, size_y, size_z, Vector3::new(0.0, 0.0, 0.0), 1.0)
+}
+
+/// Rolling hills with scattered pillars, mostly air like our worlds.
+fn terrain(size_x: usize, size_y: usize, size_z: usize) -> VoxelGrid {
+    let mut grid = VoxelGrid::new(size_x, size_y, size_z, Vector3::new(0.0, 0.0, 0.0), 1.0);
+    for z in 0..size_z {
+        for x in 0..size_x {
+            let height = 8.0 + 4.0 * (x as f64 * 0.05).sin() + 4.0 * (z as f64 * 0.07).cos();
+            for y in 0..(height as usize).min(size_y) {
+                grid.set(x, y, z, 1);
+            }
+        }
+    }
+    let mut rng = Pcg64Mcg::new(42);
+    for _ in 0..200 {
+        let x = rng.gen_range(0..size_x);
+        let z = rng.gen_range(0..size_z);
+        let height = rng.gen_range(4..size_y);
+        for y in 0..height {
+            grid.set(x, y, z, 2);
+        }
+    }
+    grid
+}
+
+/// Hash of a lattice point to 0..1 for the value noise.
+fn lattice_value(x: i64, y: i64, z: i64) -> f64 {
+    let mut hash = (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash ^= hash >> 32;
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Trilinear value noise with a lattice point every `scale` cells.
+fn value_noise(x: usize, y: usize, z: usize, scale: f64) -> f64 {
+    let position = [x as f64 / scale, y as f64 / scale, z as f64 / scale];
+    let base = position.map(|p| p.floor() as i64);
+    let fraction: [f64; 3] = std::array::from_fn(|axis| {
+        let f = position[axis] - base[axis] as f64;
+        f * f * (3.0 - 2.0 * f)
+    });
+    let mut value = 0.0;
+    for corner in 0..8 {
+        let offset = [corner & 1, corner >> 1 & 1, corner >> 2 & 1];
+        let weight: f64 = (0..3)
+            .map(|axis| {
+                if offset[axis] == 1 {
+                    fraction[axis]
+                } else {
+                    1.0 - fraction[axis]
+                }
+            })
+            .product();
+        value += weight
+            * lattice_value(
+                base[0] + offset[0],
+                base[1] + offset[1],
+                base[2] + offset[2],
+            );
+    }
+    value
+}
+
+/// Solid rock with tunnels carved out where two octaves of value noise are low, a camera
+/// above it looks into the openings in the surface.
+fn caves(size_x: usize, size_y: usize, size_z: usize) -> VoxelGrid {
+    let mut grid = VoxelGrid::new(size_x, size_y, size_z, Vector3::new(0.0, 0.0, 0.0), 1.0);
+    for y in 0..size_y {
+        for z in 0..size_z {
+            for x in 0..size_x {
+                let noise =
+                    0.7 * value_noise(x, y, z, 16.0) + 0.3 * value_noise(x + 1000, y, z, 5.0);
+                if noise > 0.45 {
+                    grid.set(x, y, z, 3);
+                }
+            }
+        }
+    }
+    grid
+}
+
+/// Blocks of tall buildings separated by streets, lots of geometry close together.
+fn city(size_x: usize, size_y: usize, size_z: usize) -> VoxelGrid {
+    const BLOCK: usize = 16;
+    const STREET: usize = 4;
+    let mut grid = VoxelGrid::new(size_x, size_y, size_z, Vector3::new(0.0, 0.0, 0.0), 1.0);
+    let mut rng = Pcg64Mcg::new(42);
+    // ground everywhere
+    for z in 0..size_z {
+        for x in 0..size_x {
+            grid.set(x, 0, z, 4);
+        }
+    }
+    for block_z in (0..size_z).step_by(BLOCK) {
+        for block_x in (0..size_x).step_by(BLOCK) {
+            // four buildings per block with a gap between them
+            let half = (BLOCK - STREET) / 2;
+            for building in 0..4 {
+                let min_x = block_x + STREET + (building & 1) * half;
+                let min_z = block_z + STREET + (building >> 1) * half;
+                let height = rng.gen_range(size_y / 4..size_y);
+                for y in 1..height {
+                    for z in min_z..(min_z + half - 1).min(size_z) {
+                        for x in min_x..(min_x + half - 1).min(size_x) {
+                            grid.set(x, y, z, 5 + building as u8);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    grid
+}
+
+/// Right and up vectors of the image plane, one unit away along `forward`.
+fn camera_basis(forward: Vector3<f64>) -> (Vector3<f64>, Vector3<f64>) {
+    let right = forward.cross(Vector3::new(0.0, 1.0, 0.0)).normalize();
+    (right, right.cross(forward))
+}
+
+/// Camera looking down on the middle of the top of the grid from `direction`, as far away as
+/// possible with every ray still entering the grid through its top.
+fn frame_top(size: [usize; 3], direction: Vector3<f64>) -> (Vector3<f64>, Vector3<f64>) {
+    let look_at = Vector3::new(size[0] as f64 / 2.0, size[1] as f64, size[2] as f64 / 2.0);
+    let direction = direction.normalize();
+    let (right, up) = camera_basis(-direction);
+    let aspect = RAYS_X as f64 / RAYS_Y as f64;
+    let mut distance = f64::INFINITY;
+    for (u, v) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+        // the rays through the image corners have to point down, where they hit the top
+        // moves away from `look_at` in proportion to the distance
+        let ray = -direction + right * u * aspect + up * v;
+        assert!(ray.y < 0.0, "view too flat to see only the top of the grid");
+        let offset = direction + ray * (direction.y / -ray.y);
+        distance = distance
+            .min(look_at.x / offset.x.abs())
+            .min(look_at.z / offset.z.abs());
+    }
+    (look_at + direction * distance, look_at)
+}
+
+/// Pinhole camera rays looking down on the scene at an angle.
+fn camera_rays(center: Vector3<f64>, look_at: Vector3<f64>) -> Vec<Ray> {
+    let forward = (look_at - center).normalize();
+    let (right, up) = camera_basis(forward);
+    let aspect = RAYS_X as f64 / RAYS_Y as f64;
+    let mut rays = Vec::new();
+    for y in 0..RAYS_Y {
+        for x in 0..RAYS_X {
+            let u = (x as f64 + 0.5) / RAYS_X as f64 * 2.0 - 1.0;
+            let v = 1.0 - (y as f64 + 0.5) / RAYS_Y as f64 * 2.0;
+            rays.push(Ray::new(center, forward + right * u * aspect + up * v));
+        }
+    }
+    rays
+}
+
+/// Traces all rays `ITERATIONS` times and prints the throughput, then once more counting the
+/// visited cells. `memory` is the size of everything the traversal reads.
+fn bench(
+    name: &str,
+    traversal: &dyn VoxelTraversal,
+    memory: usize,
+    rays: &[Ray],
+) -> Vec<Option<VoxelHit>> {
+    let mut hits = Vec::new();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        hits = rays
+            .iter()
+            .map(|ray| traversal.hit(ray, Interval::new(0.0, f64::INFINITY)))
+            .collect();
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    let rays_per_second = (rays.len() as u32 * ITERATIONS) as f64 / seconds;
+    let mut visited = 0;
+    for ray in rays {
+        traversal.hit_counted(ray, Interval::new(0.0, f64::INFINITY), &mut visited);
+    }
+    let hit_count = hits.iter().filter(|hit| hit.is_some()).count();
+    println!(
+        "{:<11} {:>12.0} rays/s {:>8.1} cells/ray {:>10.1} KiB {:>8} hits",
+        name,
+        rays_per_second,
+        visited as f64 / rays.len() as f64,
+        memory as f64 / 1024.0,
+        hit_count
+    );
+    hits
+}
+
+/// Same cell, face and material, and t up to rounding.
+fn same_hit(a: &Option<VoxelHit>, b: &Option<VoxelHit>) -> bool {
+    match (a, b) {
+        (None, None) => true,
+        (Some(a), Some(b)) => {
+            a.cell == b.cell
+                && (a.t - b.t).abs() <= 1e-9 * a.t.abs().max(1.0)
+                && a.face == b.face
+                && a.material == b.material
+        }
+        _ => false,
+    }
+}
+
+fn mismatches(a: &[Option<VoxelHit>], b: &[Option<VoxelHit>]) -> usize {
+    a.iter().zip(b).filter(|(a, b)| !same_hit(a, b)).count()
+}
+
+/// Runs every traversal on one scene and checks that they all find the same hits as the DDA.
+/// The height field only takes part on scenes where every column is solid up to its top.
+fn bench_scene(name: &str, grid: VoxelGrid, columns: bool, rays: &[Ray]) {
+    let [size_x, size_y, size_z] = grid.size();
+    let brick_map = BrickMap::from_grid(&grid);
+    let octree = SparseVoxelOctree::from_grid(&grid);
+    println!(
+        "\nscene: {} {}x{}x{}, {} bricks, {} octree nodes",
+        name,
+        size_x,
+        size_y,
+        size_z,
+        brick_map.brick_count(),
+        octree.node_count()
+    );
+
+    let grid_memory = grid.memory_footprint();
+    let flat = bench("flat", &grid, grid_memory, rays);
+    let mut results = vec![
+        (
+            "brickmap",
+            bench("brickmap", &brick_map, brick_map.memory_footprint(), rays),
+        ),
+        (
+            "octree",
+            bench("octree", &octree, octree.memory_footprint(), rays),
+        ),
+    ];
+
+    if columns {
+        let field = HeightField::from_grid(&grid);
+        let hits = bench("heightfield", &field, field.memory_footprint(), rays);
+        results.push(("heightfield", hits));
+    }
+
+    let chebyshev = DistanceFieldGrid::new(grid, Metric::Chebyshev);
+    let memory = grid_memory + chebyshev.field().memory_footprint();
+    results.push(("chebyshev", bench("chebyshev", &chebyshev, memory, rays)));
+    let manhattan = DistanceFieldGrid::new(chebyshev.into_grid(), Metric::Manhattan);
+    let memory = grid_memory + manhattan.field().memory_footprint();
+    results.push(("manhattan", bench("manhattan", &manhattan, memory, rays)));
+
+    let pyramid = OccupancyPyramid::new(manhattan.into_grid());
+    let memory = grid_memory + pyramid.memory_footprint();
+    results.push(("mip", bench("mip", &pyramid, memory, rays)));
+
+    for (name, hits) in results {
+        println!("{} mismatches: {}", name, mismatches(&flat, &hits));
+    }
+}
+
+fn main() {
+    let [size_x, size_y, size_z] = SIZE;
+    // every ray enters the grid, the far corners of the image reach its edges
+    let (center, look_at) = frame_top(SIZE, Vector3::new(-1.0, 3.0, -1.0));
+    let rays = camera_rays(center, look_at);
+    bench_scene("empty", empty(size_x, size_y, size_z), true, &rays);
+    bench_scene("terrain", terrain(size_x, size_y, size_z), true, &rays);
+    bench_scene("caves", caves(size_x, size_y, size_z), false, &rays);
+    bench_scene("city", city(size_x, size_y, size_z), true, &rays);
+}
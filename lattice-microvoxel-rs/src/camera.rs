@@ -1,10 +1,25 @@
 const ROTATION_SPEED: f32 = 0.01;
 const FREE_LOOK_MOVEMENT_SPEED: f32 = 1.0;
+const ZOOM_SPEED: f32 = 1.1;
+const NEAR: f32 = 1.0;
+const FAR: f32 = 1000.0;
+
+// elevation above the ground for a true isometric view, all three axes are shortened equally
+pub const ISOMETRIC_ELEVATION: f32 = 35.264;
+// elevation for the 2:1 dimetric view of pixel art games, a ground tile is twice as wide as high
+pub const DIMETRIC_ELEVATION: f32 = 30.0;
+
+pub enum Projection {
+    Perspective,
+    // parallel projection, zoom is half the height of the view in voxels
+    Orthographic { zoom: f32 },
+}
 
 pub struct Camera {
     position: glam::Vec3,
     direction: glam::Vec3,
     up: glam::Vec3,
+    projection: Projection,
 }
 
 impl Camera {
@@ -13,9 +28,58 @@ impl Camera {
             position: glam::Vec3::new(64.0, 40.0, 64.0),
             direction: glam::Vec3::new(0.0, -1.0, 0.0),
             up: glam::Vec3::new(0.0, 0.0, 1.0),
+            projection: Projection::Perspective,
+        }
+    }
+    // back to the free look perspective camera
+    pub fn perspective(&mut self) {
+        self.up = glam::Vec3::new(0.0, 0.0, 1.0);
+        self.projection = Projection::Perspective;
+    }
+    // orthographic view straight down on target
+    pub fn top_down(&mut self, target: glam::Vec3, zoom: f32) {
+        self.direction = glam::Vec3::new(0.0, -1.0, 0.0);
+        self.up = glam::Vec3::new(0.0, 0.0, 1.0);
+        self.position = target - self.direction * FAR / 2.0;
+        self.projection = Projection::Orthographic { zoom };
+    }
+    // orthographic view on target from 45 degrees around the vertical y axis, elevation in
+    // degrees above the ground, see ISOMETRIC_ELEVATION and DIMETRIC_ELEVATION
+    pub fn axonometric(&mut self, target: glam::Vec3, elevation: f32, zoom: f32) {
+        let (sin, cos) = elevation.to_radians().sin_cos();
+        let horizontal = glam::Vec3::new(-1.0, 0.0, -1.0).normalize();
+        self.direction = (horizontal * cos + glam::Vec3::new(0.0, -sin, 0.0)).normalize();
+        self.up = glam::Vec3::new(0.0, 1.0, 0.0);
+        self.position = target - self.direction * FAR / 2.0;
+        self.projection = Projection::Orthographic { zoom };
+    }
+    // zooms orthographic views in for positive steps and out for negative steps
+    pub fn zoom(&mut self, steps: f32) {
+        if let Projection::Orthographic { zoom } = &mut self.projection {
+            *zoom /= ZOOM_SPEED.powf(steps);
+        }
+    }
+    pub fn projection_matrix(&self, aspect_ratio: f32) -> glam::Mat4 {
+        match self.projection {
+            Projection::Perspective => glam::Mat4::perspective_rh(45.0, aspect_ratio, NEAR, FAR),
+            Projection::Orthographic { zoom } => glam::Mat4::orthographic_rh(
+                -zoom * aspect_ratio,
+                zoom * aspect_ratio,
+                -zoom,
+                zoom,
+                NEAR,
+                FAR,
+            ),
         }
     }
     pub fn update(&mut self, forward: f32, right: f32, yaw: f32, pitch: f32) {
+        if let Projection::Orthographic { zoom } = self.projection {
+            // fixed view direction, forward and right pan over the screen
+            let right_vector = self.direction.cross(self.up).normalize();
+            let up_vector = right_vector.cross(self.direction);
+            self.position += (up_vector * forward + right_vector * right) * zoom;
+            return;
+        }
         self.direction = 
             freelook_rotate(
                     self.direction,
@@ -29,6 +93,20 @@ impl Camera {
                     right * FREE_LOOK_MOVEMENT_SPEED,
                 );
     }
+    // ray through a pixel of a viewport of width by height pixels, the near and far plane points
+    // under the pixel are unprojected with the inverse of projection * view, returns the
+    // origin on the near plane and the direction to the far plane
+    pub fn pixel_ray(&self, viewport: (f32, f32), pixel: (f32, f32)) -> (glam::Vec3, glam::Vec3) {
+        let inverse = (self.projection_matrix(viewport.0 / viewport.1) * self.view_matrix()).inverse();
+        let ndc_x = 2.0 * pixel.0 / viewport.0 - 1.0;
+        let ndc_y = 1.0 - 2.0 * pixel.1 / viewport.1;
+        let near = inverse.project_point3(glam::Vec3::new(ndc_x, ndc_y, 0.0));
+        let far = inverse.project_point3(glam::Vec3::new(ndc_x, ndc_y, 1.0));
+        (near, far - near)
+    }
+    pub fn direction(&self) -> glam::Vec3 {
+        self.direction
+    }
     pub fn view_matrix(&self) -> glam::Mat4 {
         glam::Mat4::look_to_rh(self.position, self.direction, self.up)
     }
@@ -1,7 +1,17 @@
 mod camera;
 
-use std::{borrow::Cow, time::Instant};
-
+use std::{borrow::Cow, path::Path, time::Instant};
+
+use cgmath::Vector3;
+use microvoxel_raycaster::ao;
+use microvoxel_raycaster::edit::{self, CellBox};
+use microvoxel_raycaster::grid::VoxelGrid;
+use microvoxel_raycaster::palette::Palette;
+use microvoxel_raycaster::pick::{pick_ray, Pick};
+use microvoxel_raycaster::ray::Ray;
+use microvoxel_raycaster::vox;
+use microvoxel_raycaster::world_file;
+use rand::Rng;
 use wgpu::util::DeviceExt;
 use winit::{
     event::{self, Event, WindowEvent}, event_loop::EventLoop, keyboard::PhysicalKey, window::{Window, WindowBuilder}
@@ -51,37 +61,149 @@ struct Uniform {
 unsafe impl bytemuck::Pod for Uniform {}
 unsafe impl bytemuck::Zeroable for Uniform {}
 
-struct Lattice {
+// words per dirty flag, an edit uploads the whole 256 byte chunk it touched
+const DIRTY_CHUNK: usize = 64;
+
+// bytes packed four to a u32 like the lattice wants them, remembers which chunks changed
+// since the last flush so only those get written to the gpu buffer
+struct PackedBytes {
     pub data: Vec<u32>,
+    dirty: Vec<bool>,
+}
+impl PackedBytes {
+    pub fn new(len: usize) -> Self {
+        let words = len.div_ceil(4);
+        Self {
+            data: vec!(0; words),
+            dirty: vec!(false; words.div_ceil(DIRTY_CHUNK)),
+        }
+    }
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut packed = Self::new(bytes.len());
+        for (index, &value) in bytes.iter().enumerate() {
+            packed.data[index / 4] |= (value as u32) << (8 * (3 - index % 4));
+        }
+        packed
+    }
+    // the gpu buffer was created with the current data
+    pub fn mark_clean(&mut self) {
+        self.dirty.fill(false);
+    }
+    pub fn set(&mut self, index: usize, value: u8) {
+        let array_index = index / 4;
+        let u32_index = index % 4;
+        let shift = 8 * (3 - u32_index);
+        let word = self.data[array_index] & !(0xFF << shift); // zero 8 bits
+        let word = word | (value as u32) << shift;
+        if word != self.data[array_index] {
+            self.data[array_index] = word;
+            self.dirty[array_index / DIRTY_CHUNK] = true;
+        }
+    }
+    // writes runs of dirty chunks to the buffer, one write per run, returns the bytes written
+    pub fn flush(&mut self, queue: &wgpu::Queue, buffer: &wgpu::Buffer) -> usize {
+        let mut uploaded = 0;
+        let mut chunk = 0;
+        while chunk < self.dirty.len() {
+            if !self.dirty[chunk] {
+                chunk += 1;
+                continue;
+            }
+            let first = chunk;
+            while chunk < self.dirty.len() && self.dirty[chunk] {
+                self.dirty[chunk] = false;
+                chunk += 1;
+            }
+            let words = first * DIRTY_CHUNK..(chunk * DIRTY_CHUNK).min(self.data.len());
+            let offset = (words.start * std::mem::size_of::<u32>()) as wgpu::BufferAddress;
+            let bytes: &[u8] = bytemuck::cast_slice(&self.data[words]);
+            queue.write_buffer(buffer, offset, bytes);
+            uploaded += bytes.len();
+        }
+        uploaded
+    }
+}
+
+struct Lattice {
+    pub voxels: PackedBytes,
     size_x: usize,
     size_y: usize,
     size_z: usize,
 }
 impl Lattice {
+    // 8 bit index into the palette per voxel, four voxels packed in a u32
     pub fn new(size_x: usize, size_y: usize, size_z: usize) -> Self {
         Self {
-            data: vec!(0; size_x * size_y * size_z),
+            voxels: PackedBytes::new(size_x * size_y * size_z),
             size_x,
             size_y,
             size_z,
         }
     }
-/* uncomment code when we want to use 8 bits index into palette
- * pub fn set_index(&mut self, index: usize, value: u8) {
-        let array_index = index / 4;
-        let u32_index = index % 4;
-        let shift = 8 * (3 - u32_index);
-        let voxel = self.data[array_index] & !(0xFF << shift); // zero 8 bits
-        self.data[array_index] = voxel | (value as u32) << shift; 
+    pub fn set_index(&mut self, index: usize, value: u8) {
+        self.voxels.set(index, value);
     }
     pub fn set(&mut self, x: usize, y: usize, z: usize, value: u8) {
         let index = x + (z * self.size_x) + (y * self.size_x * self.size_z);
         self.set_index(index, value); 
     }
-    */
-    pub fn set(&mut self, x: usize, y: usize, z: usize, value: u32) {
-        let index = x + (z * self.size_x) + (y * self.size_x * self.size_z);
-        self.data[index] = value; 
+}
+
+// the checkerboard test pattern, palette entry 1 + xyz parity bits
+fn checkerboard(size_x: usize, size_y: usize, size_z: usize) -> (VoxelGrid, Palette) {
+    // alpha 0xBB like the color fill before the palette
+    let mut palette = Palette::new();
+    for i in 0..8u32 {
+        let r = if i & 1 != 0 { 0x000000FF } else { 0x00000000 };
+        let g = if i & 2 != 0 { 0x0000FF00 } else { 0x00000000 };
+        let b = if i & 4 != 0 { 0x00FF0000 } else { 0x00000000 };
+        palette.set(1 + i as u8, 0xBB000000 + r + g + b);
+    }
+
+    let mut grid = VoxelGrid::new(size_x, size_y, size_z, Vector3::new(0.0, 0.0, 0.0), 1.0);
+    for x in 0..size_x {
+    for y in 0..size_y {
+    for z in 0..size_z {
+        let r = if x % 2 == 0 { 1 } else { 0 };
+        let g = if y % 2 == 0 { 2 } else { 0 };
+        let b = if z % 2 == 0 { 4 } else { 0 };
+        grid.set(x, y, z, 1 + r + g + b);
+    }
+    }
+    }
+    (grid, palette)
+}
+
+// random edits per frame in the stress mode
+const STRESS_EDITS: usize = 8;
+
+// a random sphere or box somewhere in the grid, half of them carve and the others fill with
+// one of `materials`
+fn random_edit(grid: &mut VoxelGrid, rng: &mut impl Rng, materials: &[u8]) -> Option<CellBox> {
+    let size = grid.size();
+    let center: [i32; 3] = std::array::from_fn(|axis| rng.gen_range(0..size[axis] as i32));
+    let radius = rng.gen_range(1..6);
+    let material = if rng.gen_bool(0.5) { 0 } else { materials[rng.gen_range(0..materials.len())] };
+    if rng.gen_bool(0.5) {
+        let center = Vector3::new(center[0] as f64 + 0.5, center[1] as f64 + 0.5, center[2] as f64 + 0.5);
+        edit::sphere(grid, center, radius as f64, material)
+    } else {
+        edit::fill_box(grid, center.map(|c| c - radius), center.map(|c| c + radius), material)
+    }
+}
+
+// copies the edited voxels into the lattice and redoes the ambient occlusion of every voxel
+// whose corners look at them, which reaches one voxel past the region
+fn update_region(lattice: &mut Lattice, ambient_occlusion: &mut PackedBytes, grid: &VoxelGrid, region: CellBox) {
+    for [x, y, z] in region.cells() {
+        lattice.set(x, y, z, grid.get(x, y, z));
+    }
+    let [size_x, _, size_z] = grid.size();
+    for [x, y, z] in region.grow(1).intersection(&CellBox::grid(grid)).cells() {
+        let index = x + (z * size_x) + (y * size_x * size_z);
+        for (face, value) in ao::voxel_face_data(grid, x, y, z).into_iter().enumerate() {
+            ambient_occlusion.set(index * 6 + face, value);
+        }
     }
 }
 
@@ -180,6 +302,16 @@ impl LatticeHeaders {
 unsafe impl bytemuck::Pod for LatticeHeaders {}
 unsafe impl bytemuck::Zeroable for LatticeHeaders {}
 
+// voxel under a pixel of the viewport, the lattice draws voxel (x, y, z) at x..x + 1 so the ray
+// is taken to the grid's world space before tracing
+fn pick(camera: &camera::Camera, viewport: (f32, f32), pixel: (f32, f32), grid: &VoxelGrid) -> Option<Pick> {
+    let (origin, direction) = camera.pixel_ray(viewport, pixel);
+    let origin = Vector3::new(origin.x as f64, origin.y as f64, origin.z as f64);
+    let direction = Vector3::new(direction.x as f64, direction.y as f64, direction.z as f64);
+    let ray = Ray::new(grid.grid_to_world(origin), direction * grid.voxel_size());
+    pick_ray(grid, &ray)
+}
+
 fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
     let size = wgpu::Extent3d {
         width,
@@ -202,7 +334,7 @@ fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu:
     texture.create_view(&wgpu::TextureViewDescriptor::default())
 }
 
-async fn run(event_loop: EventLoop<()>, window: Window) {
+async fn run(event_loop: EventLoop<()>, window: Window, scene: Option<(VoxelGrid, Palette)>, stress: bool) {
     let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
         backends: wgpu::Backends::GL,
         ..Default::default()
@@ -220,9 +352,12 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 
     let mut mvp_uniform = Uniform::default();
 
-    let size_x = 128;//1024;
-    let size_y = 32;//128;
-    let size_z = 128;//1024;
+    let [size_x, size_y, size_z] = match &scene {
+        Some((grid, _)) => grid.size(),
+        None => [128, 32, 128], //[1024, 128, 1024]
+    };
+    // every slice set is ordered back to front for blending, the plus faces are seen from
+    // above the slice so the lowest comes first and the min faces the other way around
     let mut vertices_y_min : Vec<[f32; 3]> = Vec::new();
     let mut vertices_x_min : Vec<[f32; 3]> = Vec::new();
     let mut vertices_z_min : Vec<[f32; 3]> = Vec::new();
@@ -230,8 +365,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     let mut vertices_x_plus : Vec<[f32; 3]> = Vec::new();
     let mut vertices_z_plus : Vec<[f32; 3]> = Vec::new();
 
-    for y in 0..size_y {
-        let y = size_y - y;
+    for y in 1..=size_y {
         cube_y_plus().map(|p| vertices_y_plus.push(
                 cube_correct_and_to_float(
                     cube_offset(cube_scale(
@@ -240,7 +374,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                                [0.0, -0.001, 0.0])
                 ));
     }
-    for y in 0..size_y {
+    for y in (0..size_y).rev() {
         cube_y_min().map(|p| vertices_y_min.push(
                 cube_correct_and_to_float(
                     cube_offset(cube_scale(
@@ -249,8 +383,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                                [0.0, 0.001, 0.0])
                 ));
     }
-    for x in 0..size_x {
-        let x = size_x - x;
+    for x in 1..=size_x {
         cube_x_plus().map(|p| vertices_x_plus.push(
                 cube_correct_and_to_float(
                     cube_offset(cube_scale(
@@ -259,7 +392,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                                [-0.001, 0.0, 0.0])
                 ));
     }
-    for x in 0..size_x {
+    for x in (0..size_x).rev() {
         cube_x_min().map(|p| vertices_x_min.push(
                 cube_correct_and_to_float(
                     cube_offset(cube_scale(
@@ -268,8 +401,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                                [0.001, 0.0, 0.0])
                 ));
     }
-    for z in 0..size_z {
-        let z = size_z - z;
+    for z in 1..=size_z {
         cube_z_plus().map(|p| vertices_z_plus.push(
                 cube_correct_and_to_float(
                     cube_offset(cube_scale(
@@ -278,7 +410,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                                [0.0, 0.0, -0.001])
                 ));
     }
-    for z in 0..size_z {
+    for z in (0..size_z).rev() {
         cube_z_min().map(|p| vertices_z_min.push(
                 cube_correct_and_to_float(
                     cube_offset(cube_scale(
@@ -291,29 +423,22 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     let mut lattice = Lattice::new(size_x, size_y, size_z);
     let lattice_headers = LatticeHeaders::new(size_x as u32, size_y as u32, size_z as u32);
 
+    let (mut grid, palette) = scene.unwrap_or_else(|| checkerboard(size_x, size_y, size_z));
     for x in 0..size_x {
     for y in 0..size_y {
     for z in 0..size_z {
-        let r = if x % 2 == 0 {
-            0x000000FF
-        } else {
-            0x00000000
-        };
-        let g = if y % 2 == 0 {
-            0x0000FF00
-        } else {
-            0x00000000
-        };
-        let b = if z % 2 == 0 {
-            0x00FF0000
-        } else {
-            0x00000000
-        };
-        let c = 0xBB000000 + r + g + b;
-        lattice.set(x, y, z, c);
+        lattice.set(x, y, z, grid.get(x, y, z));
+    }
     }
     }
+    // corner occlusion of the six faces of every voxel, one byte per face
+    let mut ambient_occlusion = PackedBytes::from_bytes(&ao::face_data(&grid));
+    // the stress mode only fills with palette entries that aren't fully transparent
+    let mut materials: Vec<u8> = (1..=255).filter(|&m| palette.alpha(m) > 0.0).collect();
+    if materials.is_empty() {
+        materials.push(1);
     }
+    let mut rng = rand::thread_rng();
 
     let mut last_mouse_position : Option<(f32, f32)> = None;
     let mut current_mouse_position : Option<(f32, f32)> = None;
@@ -327,10 +452,25 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 
     let lattice_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: Some("lattice buffer"),
-        contents: bytemuck::cast_slice(lattice.data.as_slice()),
+        contents: bytemuck::cast_slice(lattice.voxels.data.as_slice()),
         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
     });
     
+    let palette_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("palette buffer"),
+        contents: bytemuck::cast_slice(&palette.colors),
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+    });
+
+    let ambient_occlusion_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("ambient occlusion buffer"),
+        contents: bytemuck::cast_slice(ambient_occlusion.data.as_slice()),
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+    });
+
+    lattice.voxels.mark_clean();
+    ambient_occlusion.mark_clean();
+
     let lattice_header_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: Some("lattice header buffer"),
         contents: bytemuck::cast_slice(&[lattice_headers]),
@@ -359,6 +499,18 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: true }, has_dynamic_offset: false, min_binding_size: None },
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: true }, has_dynamic_offset: false, min_binding_size: None },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: true }, has_dynamic_offset: false, min_binding_size: None },
+                    count: None,
+                },
             ],
         }
     );
@@ -381,6 +533,16 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                 binding: 2,
                 resource: lattice_header_buffer.as_entire_binding(),
                 
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: palette_buffer.as_entire_binding(),
+                
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: ambient_occlusion_buffer.as_entire_binding(),
+                
             }
         ],
     });
@@ -395,7 +557,10 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     let swapchain_format = swapchain_capabilities.formats[0];
     let depth_view = create_depth_texture(&device, window.inner_size().width, window.inner_size().height);
 
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+    // opaque voxels are drawn first with depth writes, the translucent ones are blended over
+    // them afterwards without writing depth so slices behind other translucent slices still
+    // reach the blend in the back to front order they are drawn in
+    let create_pipeline = |fragment_entry_point, blend, depth_write_enabled| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: None,
         layout: Some(&pipeline_layout),
         vertex: wgpu::VertexState {
@@ -416,8 +581,12 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         },
         fragment: Some(wgpu::FragmentState {
             module: &shader,
-            entry_point: "fs_main",
-            targets: &[Some(swapchain_format.into())],
+            entry_point: fragment_entry_point,
+            targets: &[Some(wgpu::ColorTargetState {
+                format: swapchain_format,
+                blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
             compilation_options: wgpu::PipelineCompilationOptions::default(),
         }),
         primitive: wgpu::PrimitiveState {
@@ -429,7 +598,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         },
         depth_stencil: Some(wgpu::DepthStencilState {
             format: wgpu::TextureFormat::Depth32Float,
-            depth_write_enabled: true,
+            depth_write_enabled,
             depth_compare: wgpu::CompareFunction::Less,
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
@@ -437,6 +606,8 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         multisample: wgpu::MultisampleState::default(),
         multiview: None,
     });
+    let opaque_pipeline = create_pipeline("fs_opaque", Some(wgpu::BlendState::REPLACE), true);
+    let translucent_pipeline = create_pipeline("fs_translucent", Some(wgpu::BlendState::ALPHA_BLENDING), false);
 
     let mut config = surface
         .get_default_config(&adapter, window.inner_size().width, window.inner_size().height)
@@ -444,8 +615,10 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     surface.configure(&device, &config);
 
     let window = &window;
-    mvp_uniform.projection = glam::Mat4::perspective_rh(45.0, window.inner_size().width as f32 / window.inner_size().height as f32, 1.0, 1000.0 );
     let mut camera = camera::Camera::new();
+    let aspect_ratio = window.inner_size().width as f32 / window.inner_size().height as f32;
+    let lattice_center = glam::Vec3::new(size_x as f32, size_y as f32, size_z as f32) / 2.0;
+    let zoom = size_x.max(size_z) as f32 / 2.0;
 
     let vertex_buffer_y_min = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: Some("Vertex Buffer"),
@@ -478,6 +651,17 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         usage: wgpu::BufferUsages::VERTEX,
     });
 
+    // vertex buffers and vertex counts of the slice sets in the order of Face: -x, +x, -y, +y,
+    // -z, +z, the index is passed to the shader as the instance index
+    let slice_sets = [
+        (&vertex_buffer_x_min, vertices_x_min.len() as u32),
+        (&vertex_buffer_x_plus, vertices_x_plus.len() as u32),
+        (&vertex_buffer_y_min, vertices_y_min.len() as u32),
+        (&vertex_buffer_y_plus, vertices_y_plus.len() as u32),
+        (&vertex_buffer_z_min, vertices_z_min.len() as u32),
+        (&vertex_buffer_z_plus, vertices_z_plus.len() as u32),
+    ];
+
     let mut fps = Fps::new(10);
 
     event_loop
@@ -502,9 +686,21 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                         window.request_redraw();
                     }
                     WindowEvent::RedrawRequested => {
+                        mvp_uniform.projection = camera.projection_matrix(aspect_ratio);
                         mvp_uniform.view = camera.view_matrix();
                         queue.write_buffer(&uniform_buffer, 0, bytemuck::cast_slice(&[mvp_uniform]));
 
+                        if stress {
+                            for _ in 0..STRESS_EDITS {
+                                if let Some(region) = random_edit(&mut grid, &mut rng, &materials) {
+                                    update_region(&mut lattice, &mut ambient_occlusion, &grid, region);
+                                }
+                            }
+                        }
+                        // only the chunks edited since the last frame go to the gpu
+                        let uploaded = lattice.voxels.flush(&queue, &lattice_buffer)
+                            + ambient_occlusion.flush(&queue, &ambient_occlusion_buffer);
+
                         let frame = surface
                             .get_current_texture()
                             .expect("Failed to acquire next swap chain texture");
@@ -540,20 +736,30 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                                 });
                             fps.add_timepoint();
                             println!("fps: {}", fps.value());
-                            rpass.set_pipeline(&render_pipeline);
+                            if stress {
+                                println!(
+                                    "upload: {:.1} KiB/frame, {:.2} MiB/s",
+                                    uploaded as f64 / 1024.0,
+                                    (uploaded * fps.value()) as f64 / (1024.0 * 1024.0)
+                                );
+                            }
                             rpass.set_bind_group(0, &mvp_bind_group, &[]);
-                            rpass.set_vertex_buffer(0, vertex_buffer_y_min.slice(..));
-                            rpass.draw(0..vertices_y_min.len() as u32, 0..1);
-                            rpass.set_vertex_buffer(0, vertex_buffer_x_min.slice(..));
-                            rpass.draw(0..vertices_x_min.len() as u32, 0..1);
-                            rpass.set_vertex_buffer(0, vertex_buffer_z_min.slice(..));
-                            rpass.draw(0..vertices_z_min.len() as u32, 0..1);
-                            rpass.set_vertex_buffer(0, vertex_buffer_y_plus.slice(..));
-                            rpass.draw(0..vertices_y_plus.len() as u32, 3..4);
-                            rpass.set_vertex_buffer(0, vertex_buffer_x_plus.slice(..));
-                            rpass.draw(0..vertices_x_plus.len() as u32, 0..1);
-                            rpass.set_vertex_buffer(0, vertex_buffer_z_plus.slice(..));
-                            rpass.draw(0..vertices_z_plus.len() as u32, 0..1);
+                            // slices of different axes can't be sorted against each other,
+                            // draw the axis the camera looks along the most last so its
+                            // slices blend over the others
+                            let direction = camera.direction();
+                            let mut axes = [0, 1, 2];
+                            axes.sort_by(|a, b| direction[*a].abs().total_cmp(&direction[*b].abs()));
+                            for pipeline in [&opaque_pipeline, &translucent_pipeline] {
+                                rpass.set_pipeline(pipeline);
+                                for axis in axes {
+                                    for face in [2 * axis, 2 * axis + 1] {
+                                        let (vertex_buffer, vertex_count) = slice_sets[face];
+                                        rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                                        rpass.draw(0..vertex_count, face as u32..face as u32 + 1);
+                                    }
+                                }
+                            }
                         }
 
                         queue.submit(Some(encoder.finish()));
@@ -572,6 +778,12 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                                         "a" => camera.update(0.0, -0.1, 0.0, 0.0),
                                         "s" => camera.update(-0.1, 0.0, 0.0, 0.0),
                                         "d" => camera.update(0.0, 0.1, 0.0, 0.0),
+                                        "q" => camera.zoom(-1.0),
+                                        "e" => camera.zoom(1.0),
+                                        "1" => camera.perspective(),
+                                        "2" => camera.top_down(lattice_center, zoom),
+                                        "3" => camera.axonometric(lattice_center, camera::ISOMETRIC_ELEVATION, zoom),
+                                        "4" => camera.axonometric(lattice_center, camera::DIMETRIC_ELEVATION, zoom),
                                         _ => ()
                                     }
                                     _ => ()
@@ -580,6 +792,19 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                             _ => ()
                         }
                     }
+                    WindowEvent::MouseInput { state: event::ElementState::Pressed, button: event::MouseButton::Left, .. } => {
+                        let Some(pixel) = current_mouse_position else {
+                            return;
+                        };
+                        let viewport = (window.inner_size().width as f32, window.inner_size().height as f32);
+                        match pick(&camera, viewport, pixel, &grid) {
+                            Some(pick) => println!(
+                                "voxel {:?} material {} face {:?} normal {:?} adjacent {:?}",
+                                pick.cell, pick.material, pick.face, pick.normal(), pick.adjacent
+                            ),
+                            None => println!("nothing under the cursor"),
+                        }
+                    }
                     WindowEvent::CursorMoved { position, .. } => {
                         current_mouse_position = Some((position.x as f32, position.y as f32));
                         if let (Some(last_mouse_position), Some(current_mouse_position)) = (last_mouse_position, current_mouse_position) {
@@ -597,9 +822,25 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 }
 
 fn main() {
+    // optional world (.mvxw) or MagicaVoxel (.vox) file to show instead of the checkerboard,
+    // --stress makes random edits every frame and prints the upload bandwidth
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let stress = args.iter().any(|arg| arg == "--stress");
+    args.retain(|arg| arg != "--stress");
+    let scene = args.first().cloned().map(|path| {
+        let scene = if path.ends_with(".mvxw") {
+            world_file::load(Path::new(&path)).map_err(|error| error.to_string())
+        } else {
+            vox::load(Path::new(&path)).map_err(|error| error.to_string())
+        };
+        scene.unwrap_or_else(|error| {
+            eprintln!("error: can't load {}: {}", path, error);
+            std::process::exit(1);
+        })
+    });
     let event_loop = EventLoop::new().unwrap();
     let window = WindowBuilder::new().with_title("microvoxel").with_resizable(false).build(&event_loop).unwrap();
     
     env_logger::init();
-    pollster::block_on(run(event_loop, window));
+    pollster::block_on(run(event_loop, window, scene, stress));
 }
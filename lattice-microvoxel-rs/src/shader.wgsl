@@ -25,7 +25,23 @@ var<storage, read> lattice : Lattice;
 @group(0) @binding(2)
 var<storage, read> lattice_headers : LatticeHeaders;
 
-/* uncomment when using 8 bit with palette
+// 256 colors packed as 0xAABBGGRR, indexed by the lattice
+struct Palette {
+    colors: array<u32, 256>,
+};
+
+@group(0) @binding(3)
+var<storage, read> palette : Palette;
+
+// corner occlusion of the six faces of every voxel, one byte per face packed like the lattice,
+// 2 bits per corner from 0 fully occluded to 3 open
+struct AmbientOcclusion {
+    data: array<u32>,
+};
+
+@group(0) @binding(4)
+var<storage, read> ambient_occlusion : AmbientOcclusion;
+
 fn lattice_get_index(index: u32) -> u32 {
     var array_index = index / 4;
     var u32_index = index % 4;
@@ -39,14 +55,23 @@ fn lattice_get(x: u32, y: u32, z: u32) -> u32 {
     var index = x + (z * size_x) + (y * size_x * size_z);
     return lattice_get_index(index); 
 }
-*/
 
-fn lattice_get(x: u32, y: u32, z: u32) -> u32 {
+// corner occlusion levels of a face of voxel x, y, z, face in the order -x, +x, -y, +y, -z, +z
+fn ambient_occlusion_get(x: u32, y: u32, z: u32, face: u32) -> u32 {
     var size_x : u32 = lattice_headers.size_x;
-    var size_y : u32 = lattice_headers.size_y;
     var size_z : u32 = lattice_headers.size_z;
-    var index = x + (z * size_x) + (y * size_x * size_z);
-    return lattice.data[index]; 
+    var index = (x + (z * size_x) + (y * size_x * size_z)) * 6 + face;
+    return (ambient_occlusion.data[index / 4] >> (8u * (3 - index % 4))) & 0xFFu;
+}
+
+// bilinear interpolation of the corners (-u, -v), (+u, -v), (-u, +v), (+u, +v) of a face,
+// u and v are the two axes along the face in x, y, z order
+fn ambient_occlusion_interpolate(corners: u32, uv: vec2<f32>) -> f32 {
+    let c00 = f32(corners & 3u);
+    let c10 = f32((corners >> 2) & 3u);
+    let c01 = f32((corners >> 4) & 3u);
+    let c11 = f32((corners >> 6) & 3u);
+    return mix(mix(c00, c10, uv.x), mix(c01, c11, uv.x), uv.y) / 3.0;
 }
 
 fn unpack_rgba(color: u32) -> vec4<f32> {
@@ -64,6 +89,7 @@ struct VertexInput {
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) vert_pos: vec3<f32>,
+    @location(1) @interpolate(flat) face: u32,
 }
 
 @vertex
@@ -71,15 +97,48 @@ fn vs_main(input: VertexInput, @builtin(vertex_index) in_vertex_index: u32, @bui
     var out: VertexOutput;
     out.clip_position = mvp.projection * mvp.view * mvp.world * vec4f(input.position, 1.0);
     out.vert_pos = input.position;
+    out.face = in_instance_index;
     return out;
 }
 
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+fn shade(in: VertexOutput) -> vec4<f32> {
     let x = u32(in.vert_pos.x);
     let y = u32(in.vert_pos.y);
     let z = u32(in.vert_pos.z);
-    return unpack_rgba(lattice_get(x, y, z));
+    let color = unpack_rgba(palette.colors[lattice_get(x, y, z)]);
+    // palette entry 0 is empty space
+    if color.a == 0.0 {
+        discard;
+    }
+    var uv: vec2<f32>;
+    switch in.face / 2u {
+        case 0u: { uv = fract(in.vert_pos.yz); }
+        case 1u: { uv = fract(in.vert_pos.xz); }
+        default: { uv = fract(in.vert_pos.xy); }
+    }
+    let occlusion = ambient_occlusion_interpolate(ambient_occlusion_get(x, y, z, in.face), uv);
+    // the darkest corners lose half of the light like in the raycaster
+    return vec4<f32>(color.rgb * (1.0 - 0.5 * (1.0 - occlusion)), color.a);
 //    return vec4<f32>((in.vert_pos + 1.5) / 10.0, 1.0);
 //    return vec4<f32>(1.0, 0.0, 0.0, 1.0);
 }
+
+// first pass, only fully opaque voxels with depth writes
+@fragment
+fn fs_opaque(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = shade(in);
+    if color.a < 1.0 {
+        discard;
+    }
+    return color;
+}
+
+// second pass, translucent voxels blended back to front over the opaque ones
+@fragment
+fn fs_translucent(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = shade(in);
+    if color.a == 1.0 {
+        discard;
+    }
+    return color;
+}
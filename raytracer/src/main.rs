@@ -1,17 +1,18 @@
-use image::{RgbImage, ImageBuffer, Rgb};
+use image::Rgb;
 use cgmath::Vector3;
 use cgmath::InnerSpace;
 use cgmath::VectorSpace;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
 use rand_pcg::Pcg64Mcg;
+use microvoxel_raycaster::render::{default_threads, render_tiles};
 
 struct Random {
     rng : Box<Pcg64Mcg>,
 }
 
 impl Random {
-    pub fn new() -> Self {
-        let rng = Box::new(Pcg64Mcg::new(42));
+    pub fn new(seed: u64) -> Self {
+        let rng = Box::new(Pcg64Mcg::seed_from_u64(seed));
         Self {
             rng,
         }
@@ -209,15 +210,17 @@ fn main() {
     let viewport_upper_left = CAMERA_CENTER - Vector3::new(0.0, 0.0, FOCAL_LENGTH) - viewport_u / 2.0 - viewport_v / 2.0;
     let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
     
-    let mut buffer : RgbImage = ImageBuffer::new(IMAGE_WIDTH, IMAGE_HEIGHT);
+    const SEED: u64 = 42;
+    let threads = default_threads();
 
-    let mut random = Random::new();
     let mut world = Vec::new();
     world.push(Sphere::new(Vector3::new(0.0, 0.0, -1.0), 0.5));
     world.push(Sphere::new(Vector3::new(0.0, -100.5, -1.0), 100.0));
     let world = world;
 
-    for (x, y, pixel) in buffer.enumerate_pixels_mut() {
+    let buffer = render_tiles(IMAGE_WIDTH, IMAGE_HEIGHT, threads, |x, y| {
+        // every pixel gets its own random stream so it does not matter which thread renders it
+        let mut random = Random::new(SEED + (y * IMAGE_WIDTH + x) as u64);
         let mut color = Vector3::new(0.0, 0.0, 0.0);
         for _ in 0..SAMPLES_PER_PIXEL {
             let offset = random.sample_square();
@@ -233,7 +236,7 @@ fn main() {
         let ig = (256.0 * INTENSITY.clamp(color.y)) as u8;
         let ib = (256.0 * INTENSITY.clamp(color.z)) as u8;
 
-        *pixel = Rgb([ir, ig, ib]);
-    }
+        Rgb([ir, ig, ib])
+    });
     buffer.save("render.png").unwrap();
 }
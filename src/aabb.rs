@@ -0,0 +1,38 @@
+use cgmath::Vector3;
+
+use crate::interval::Interval;
+use crate::ray::Ray;
+
+/// Axis aligned bounding box.
+pub struct Aabb {
+    pub min: Vector3<f64>,
+    pub max: Vector3<f64>,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3<f64>, max: Vector3<f64>) -> Self {
+        Self { min, max }
+    }
+    /// Slab test, returns the part of `ray_t` for which the ray is inside the box.
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<Interval> {
+        let mut t = ray_t;
+        for axis in 0..3 {
+            if ray.dir[axis] == 0.0 {
+                // parallel to the slab, either always or never inside of it
+                if ray.origin[axis] < self.min[axis] || ray.origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let inv_dir = 1.0 / ray.dir[axis];
+            let t0 = (self.min[axis] - ray.origin[axis]) * inv_dir;
+            let t1 = (self.max[axis] - ray.origin[axis]) * inv_dir;
+            let (t0, t1) = if inv_dir < 0.0 { (t1, t0) } else { (t0, t1) };
+            t = Interval::new(t.min.max(t0), t.max.min(t1));
+            if t.min > t.max {
+                return None;
+            }
+        }
+        Some(t)
+    }
+}
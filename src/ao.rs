@@ -0,0 +1,103 @@
+use cgmath::Vector3;
+
+use crate::grid::VoxelGrid;
+use crate::traversal::{Face, VoxelHit};
+
+/// Ambient occlusion level of a face corner with no neighbouring voxels.
+pub const OPEN: u8 = 3;
+
+/// Occlusion level of one face corner from the three voxels next to it in front of the face,
+/// 0 is fully occluded and 3 is open. Two solid sides close the corner off no matter what is
+/// diagonally across.
+pub fn corner_occlusion(side1: bool, side2: bool, corner: bool) -> u8 {
+    if side1 && side2 {
+        0
+    } else {
+        OPEN - (side1 as u8 + side2 as u8 + corner as u8)
+    }
+}
+
+/// The two axes spanning faces along `axis`, in x, y, z order.
+fn face_axes(axis: usize) -> (usize, usize) {
+    match axis {
+        0 => (1, 2),
+        1 => (0, 2),
+        _ => (0, 1),
+    }
+}
+
+/// Occlusion levels of the four corners of `face` of `cell`, ordered (-u, -v), (+u, -v),
+/// (-u, +v), (+u, +v) where u and v are the two axes along the face in x, y, z order.
+/// Cells outside of the grid count as empty.
+pub fn face_corners(grid: &VoxelGrid, cell: [i32; 3], face: Face) -> [u8; 4] {
+    let axis = face.axis();
+    let (u, v) = face_axes(axis);
+    // layer of cells the face looks into
+    let mut front = cell;
+    front[axis] += face.normal()[axis] as i32;
+    let solid = |du: i32, dv: i32| {
+        let mut neighbour = front;
+        neighbour[u] += du;
+        neighbour[v] += dv;
+        grid.get_checked(neighbour[0], neighbour[1], neighbour[2])
+            .is_some_and(|material| material != 0)
+    };
+    [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+        .map(|(du, dv)| corner_occlusion(solid(du, 0), solid(0, dv), solid(du, dv)))
+}
+
+/// Bilinear interpolation of the corner levels at (`u`, `v`) in 0..1 across the face,
+/// returns 0 for fully occluded up to 1 for open.
+pub fn interpolate(corners: [u8; 4], u: f64, v: f64) -> f64 {
+    let [c00, c10, c01, c11] = corners.map(|level| level as f64 / OPEN as f64);
+    let bottom = c00 + (c10 - c00) * u;
+    let top = c01 + (c11 - c01) * u;
+    bottom + (top - bottom) * v
+}
+
+/// Ambient occlusion at world space `point` on the face of `hit`, 0 for fully occluded up to 1
+/// for open. Hits without a face are inside of a voxel and count as open.
+pub fn occlusion(grid: &VoxelGrid, hit: &VoxelHit, point: Vector3<f64>) -> f64 {
+    let Some(face) = hit.face else {
+        return 1.0;
+    };
+    let (u, v) = face_axes(face.axis());
+    let position = grid.world_to_grid(point);
+    let fraction = |axis: usize| (position[axis] - hit.cell[axis] as f64).clamp(0.0, 1.0);
+    interpolate(face_corners(grid, hit.cell, face), fraction(u), fraction(v))
+}
+
+/// Packs the four corner levels of a face into a byte, corner i in bits 2i and 2i + 1.
+pub fn pack_corners(corners: [u8; 4]) -> u8 {
+    corners
+        .iter()
+        .enumerate()
+        .fold(0, |packed, (i, &level)| packed | level << (2 * i))
+}
+
+/// Packed corner levels of the six faces of the voxel at (x, y, z) in `Face` order, all 0 when
+/// the voxel is empty.
+pub fn voxel_face_data(grid: &VoxelGrid, x: usize, y: usize, z: usize) -> [u8; 6] {
+    if grid.get(x, y, z) == 0 {
+        return [0; 6];
+    }
+    let cell = [x as i32, y as i32, z as i32];
+    Face::ALL.map(|face| pack_corners(face_corners(grid, cell, face)))
+}
+
+/// Packed corner levels of all six faces of every voxel for the GPU, six bytes per voxel in
+/// grid index order and `Face` order within a voxel. Empty voxels are left at 0.
+pub fn face_data(grid: &VoxelGrid) -> Vec<u8> {
+    let [size_x, size_y, size_z] = grid.size();
+    let mut data = vec![0; size_x * size_y * size_z * Face::ALL.len()];
+    for y in 0..size_y {
+        for z in 0..size_z {
+            for x in 0..size_x {
+                let index = x + (z * size_x) + (y * size_x * size_z);
+                data[index * Face::ALL.len()..(index + 1) * Face::ALL.len()]
+                    .copy_from_slice(&voxel_face_data(grid, x, y, z));
+            }
+        }
+    }
+    data
+}
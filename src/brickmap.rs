@@ -0,0 +1,257 @@
+use cgmath::Vector3;
+
+use crate::grid::VoxelGrid;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::traversal::{grid_entry, VoxelHit, VoxelRayIter, VoxelTraversal};
+
+pub const BRICK_SIZE: usize = 4;
+const NO_BRICK: u32 = u32::MAX;
+
+/// 4x4x4 voxels, the occupancy of all of them fits in one u64 so an empty brick is one read.
+#[derive(Clone)]
+pub struct Brick {
+    pub mask: u64,
+    pub materials: [u8; 64],
+}
+
+impl Brick {
+    pub fn bit(x: usize, y: usize, z: usize) -> usize {
+        x + (z * BRICK_SIZE) + (y * BRICK_SIZE * BRICK_SIZE)
+    }
+}
+
+/// Two level voxel storage: a coarse grid of brick pointers into a pool of bricks.
+/// Regions without any voxels don't get a brick.
+pub struct BrickMap {
+    bricks: Vec<Brick>,
+    map: Vec<u32>,
+    size: [usize; 3],
+    size_in_bricks: [usize; 3],
+    origin: Vector3<f64>,
+    voxel_size: f64,
+}
+
+impl BrickMap {
+    pub fn new(
+        size_x: usize,
+        size_y: usize,
+        size_z: usize,
+        origin: Vector3<f64>,
+        voxel_size: f64,
+    ) -> Self {
+        let size_in_bricks = [
+            size_x.div_ceil(BRICK_SIZE),
+            size_y.div_ceil(BRICK_SIZE),
+            size_z.div_ceil(BRICK_SIZE),
+        ];
+        Self {
+            bricks: Vec::new(),
+            map: vec![NO_BRICK; size_in_bricks.iter().product()],
+            size: [size_x, size_y, size_z],
+            size_in_bricks,
+            origin,
+            voxel_size,
+        }
+    }
+    pub fn from_grid(grid: &VoxelGrid) -> Self {
+        let [size_x, size_y, size_z] = grid.size();
+        let mut brick_map = Self::new(size_x, size_y, size_z, grid.origin(), grid.voxel_size());
+        for y in 0..size_y {
+            for z in 0..size_z {
+                for x in 0..size_x {
+                    let value = grid.get(x, y, z);
+                    if value != 0 {
+                        brick_map.set(x, y, z, value);
+                    }
+                }
+            }
+        }
+        brick_map
+    }
+    pub fn size(&self) -> [usize; 3] {
+        self.size
+    }
+    pub fn brick_count(&self) -> usize {
+        self.bricks.len()
+    }
+    /// Bytes used by the brick pointers and the bricks.
+    pub fn memory_footprint(&self) -> usize {
+        self.map.len() * std::mem::size_of::<u32>()
+            + self.bricks.len() * std::mem::size_of::<Brick>()
+    }
+    fn map_index(&self, x: usize, y: usize, z: usize) -> usize {
+        let [size_x, _, size_z] = self.size_in_bricks;
+        x + (z * size_x) + (y * size_x * size_z)
+    }
+    /// Brick at brick coordinates, None when the region is empty.
+    pub fn brick(&self, x: usize, y: usize, z: usize) -> Option<&Brick> {
+        match self.map[self.map_index(x, y, z)] {
+            NO_BRICK => None,
+            index => Some(&self.bricks[index as usize]),
+        }
+    }
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        let size = BRICK_SIZE;
+        match self.brick(x / size, y / size, z / size) {
+            Some(brick) => brick.materials[Brick::bit(x % size, y % size, z % size)],
+            None => 0,
+        }
+    }
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: u8) {
+        let size = BRICK_SIZE;
+        let map_index = self.map_index(x / size, y / size, z / size);
+        if self.map[map_index] == NO_BRICK {
+            if value == 0 {
+                return;
+            }
+            self.map[map_index] = self.bricks.len() as u32;
+            self.bricks.push(Brick {
+                mask: 0,
+                materials: [0; 64],
+            });
+        }
+        let brick = &mut self.bricks[self.map[map_index] as usize];
+        let bit = Brick::bit(x % size, y % size, z % size);
+        brick.materials[bit] = value;
+        if value == 0 {
+            brick.mask &= !(1 << bit);
+        } else {
+            brick.mask |= 1 << bit;
+        }
+    }
+}
+
+impl VoxelTraversal for BrickMap {
+    /// Hierarchical DDA, steps over bricks and only walks the cells of bricks with voxels.
+    fn hit_counted(&self, ray: &Ray, ray_t: Interval, visited: &mut usize) -> Option<VoxelHit> {
+        let ray = Ray::new(
+            (ray.origin - self.origin) / self.voxel_size,
+            ray.dir / self.voxel_size,
+        );
+        // both DDA levels start from the clipped ray, where rounding can lose the plane the ray
+        // came in through, so the entry face is taken from the unclipped ray once
+        let (ray_t, _, entry_face) = grid_entry(&ray, ray_t, self.size)?;
+        let mut first_brick = true;
+        // in brick space one unit is one brick, t stays the same
+        let brick_size = BRICK_SIZE as f64;
+        let brick_ray = Ray::new(ray.origin / brick_size, ray.dir / brick_size);
+        for brick_step in VoxelRayIter::clipped(&brick_ray, ray_t, self.size_in_bricks) {
+            *visited += 1;
+            let brick_face = if first_brick {
+                entry_face
+            } else {
+                brick_step.face
+            };
+            first_brick = false;
+            let [x, y, z] = brick_step.cell;
+            let Some(brick) = self.brick(x as usize, y as usize, z as usize) else {
+                continue;
+            };
+            if brick.mask == 0 {
+                continue;
+            }
+            let brick_origin = Vector3::new(x as f64, y as f64, z as f64) * brick_size;
+            let local_ray = Ray::new(ray.origin - brick_origin, ray.dir);
+            let brick_t = Interval::new(brick_step.t_enter, brick_step.t_exit);
+            let steps = VoxelRayIter::clipped(&local_ray, brick_t, [BRICK_SIZE; 3]);
+            for (index, step) in steps.enumerate() {
+                *visited += 1;
+                // the first cell of a brick is entered through the brick's face, the others
+                // through the face the in-brick DDA stepped across
+                let face = if index == 0 { brick_face } else { step.face };
+                let [lx, ly, lz] = step.cell;
+                let bit = Brick::bit(lx as usize, ly as usize, lz as usize);
+                if brick.mask & (1 << bit) != 0 {
+                    return Some(VoxelHit {
+                        cell: [
+                            x * BRICK_SIZE as i32 + lx,
+                            y * BRICK_SIZE as i32 + ly,
+                            z * BRICK_SIZE as i32 + lz,
+                        ],
+                        t: step.t_enter,
+                        face,
+                        material: brick.materials[bit],
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::InnerSpace;
+    use rand::Rng;
+    use rand_pcg::Pcg64Mcg;
+
+    use super::*;
+
+    /// Random rays from outside and inside of random grids, the brick map has to find the same
+    /// cell, t, face and material as the flat DDA. Most sizes are not multiples of the brick
+    /// size so the last bricks stick out of the grid.
+    #[test]
+    fn hits_match_the_grid() {
+        let mut rng = Pcg64Mcg::new(9);
+        for size in [[35, 7, 35], [13, 5, 9], [16, 8, 12], [1, 3, 2]] {
+            let [size_x, size_y, size_z] = size;
+            let origin = Vector3::new(-2.0, 0.5, 3.0);
+            let mut grid = VoxelGrid::new(size_x, size_y, size_z, origin, 0.5);
+            for y in 0..size_y {
+                for z in 0..size_z {
+                    for x in 0..size_x {
+                        if rng.gen_bool(0.05) {
+                            grid.set(x, y, z, rng.gen_range(1..=255));
+                        }
+                    }
+                }
+            }
+            let brick_map = BrickMap::from_grid(&grid);
+            let extent = size.map(|s| s as f64 * 0.5);
+            let center = origin + Vector3::new(extent[0], extent[1], extent[2]) / 2.0;
+            for i in 0..5000 {
+                let target = origin
+                    + Vector3::new(
+                        rng.gen_range(0.0..extent[0]),
+                        rng.gen_range(0.0..extent[1]),
+                        rng.gen_range(0.0..extent[2]),
+                    );
+                let start = if i % 4 == 0 {
+                    // inside of the grid
+                    origin
+                        + Vector3::new(
+                            rng.gen_range(0.0..extent[0]),
+                            rng.gen_range(0.0..extent[1]),
+                            rng.gen_range(0.0..extent[2]),
+                        )
+                } else {
+                    let direction = Vector3::new(
+                        rng.gen_range(-1.0..1.0),
+                        rng.gen_range(-1.0..1.0),
+                        rng.gen_range(-1.0..1.0),
+                    );
+                    center + direction.normalize() * 40.0
+                };
+                let ray = Ray::new(start, target - start);
+                let expected = grid.hit(&ray, Interval::new(0.0, f64::INFINITY));
+                let hit = brick_map.hit(&ray, Interval::new(0.0, f64::INFINITY));
+                match (expected, hit) {
+                    (None, None) => {}
+                    (Some(expected), Some(hit)) => {
+                        assert_eq!(hit.cell, expected.cell, "{:?} {:?}", start, target);
+                        assert!(
+                            (hit.t - expected.t).abs() < 1e-9,
+                            "{:?} {:?}",
+                            start,
+                            target
+                        );
+                        assert_eq!(hit.face, expected.face, "{:?} {:?}", start, target);
+                        assert_eq!(hit.material, expected.material);
+                    }
+                    _ => panic!("{:?} {:?} {:?} {:?}", start, target, expected, hit),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::ray::Ray;
+
+/// Elevation above the ground in degrees for a true isometric view, all three axes are
+/// foreshortened equally.
+pub const ISOMETRIC_ELEVATION: f64 = 35.264389682754654;
+/// Elevation above the ground in degrees for the 2:1 dimetric view of pixel art games, a
+/// ground tile is twice as wide as it is high.
+pub const DIMETRIC_ELEVATION: f64 = 30.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Projection {
+    /// Pinhole camera, `fov` is the vertical field of view in degrees.
+    Perspective { fov: f64 },
+    /// Parallel rays, `zoom` is half the height of the view in world units.
+    Orthographic { zoom: f64 },
+}
+
+/// Camera producing one ray per pixel through the pixel centers. Perspective rays all start at
+/// the camera and pass through an image plane at distance 1, orthographic rays start on the
+/// plane through the camera and all point the same way.
+pub struct Camera {
+    pub center: Vector3<f64>,
+    width: u32,
+    height: u32,
+    forward: Vector3<f64>,
+    orthographic: bool,
+    pixel00_loc: Vector3<f64>,
+    pixel_delta_u: Vector3<f64>,
+    pixel_delta_v: Vector3<f64>,
+}
+
+impl Camera {
+    pub fn new(
+        width: u32,
+        height: u32,
+        center: Vector3<f64>,
+        look_at: Vector3<f64>,
+        projection: Projection,
+    ) -> Self {
+        const FOCAL_LENGTH: f64 = 1.0;
+
+        let (viewport_height, focal_length) = match projection {
+            Projection::Perspective { fov } => (
+                2.0 * (fov.to_radians() / 2.0).tan() * FOCAL_LENGTH,
+                FOCAL_LENGTH,
+            ),
+            Projection::Orthographic { zoom } => (2.0 * zoom, 0.0),
+        };
+        let viewport_width = viewport_height * width as f64 / height as f64;
+
+        // camera basis, w points backwards away from what the camera looks at, looking straight
+        // down the top of the image points to -z
+        let w = (center - look_at).normalize();
+        let up = if w.x == 0.0 && w.z == 0.0 {
+            Vector3::new(0.0, 0.0, -w.y.signum())
+        } else {
+            Vector3::new(0.0, 1.0, 0.0)
+        };
+        let u = up.cross(w).normalize();
+        let v = w.cross(u);
+
+        let viewport_u = viewport_width * u;
+        let viewport_v = viewport_height * -v;
+
+        let pixel_delta_u = viewport_u / width as f64;
+        let pixel_delta_v = viewport_v / height as f64;
+
+        let viewport_upper_left = center - focal_length * w - viewport_u / 2.0 - viewport_v / 2.0;
+        let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
+        Self {
+            center,
+            width,
+            height,
+            forward: -w,
+            orthographic: matches!(projection, Projection::Orthographic { .. }),
+            pixel00_loc,
+            pixel_delta_u,
+            pixel_delta_v,
+        }
+    }
+    /// Width and height of the image in pixels.
+    pub fn viewport(&self) -> [u32; 2] {
+        [self.width, self.height]
+    }
+    /// Distance of `point` in front of the camera along the view direction, the linear depth
+    /// for both projections.
+    pub fn depth(&self, point: Vector3<f64>) -> f64 {
+        (point - self.center).dot(self.forward)
+    }
+    /// Ray through the center of pixel (x, y), y goes down the image.
+    pub fn get_ray(&self, x: u32, y: u32) -> Ray {
+        let pixel_sample =
+            self.pixel00_loc + (x as f64 * self.pixel_delta_u) + (y as f64 * self.pixel_delta_v);
+        if self.orthographic {
+            Ray::new(pixel_sample, self.forward)
+        } else {
+            Ray::new(self.center, pixel_sample - self.center)
+        }
+    }
+}
+
+/// Camera position `distance` away from `target`, turned 45 degrees around the vertical axis
+/// and `elevation` degrees above the ground. With an orthographic projection this gives the
+/// isometric and dimetric game views.
+pub fn axonometric_position(target: Vector3<f64>, distance: f64, elevation: f64) -> Vector3<f64> {
+    let (sin, cos) = elevation.to_radians().sin_cos();
+    target + distance * Vector3::new(cos * 0.5f64.sqrt(), sin, cos * 0.5f64.sqrt())
+}
@@ -0,0 +1,286 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use cgmath::Vector3;
+use image::ImageFormat;
+use microvoxel_raycaster::gbuffer::GBufferFormat;
+use microvoxel_raycaster::light::Light;
+
+pub const USAGE: &str = "\
+Usage: microvoxel-raycaster [options]
+
+Options:
+  --width <pixels>            image width [default: 400]
+  --height <pixels>           image height [default: width / (16 / 9)]
+  --camera <x,y,z>            camera position [default: 0,0,4]
+  --look-at <x,y,z>           point the camera looks at [default: 0,0,3], isometric and
+                              dimetric views look at it from the distance of the camera
+  --projection <projection>   perspective, orthographic, isometric or dimetric
+                              [default: perspective]
+  --fov <degrees>             vertical field of view of the perspective projection [default: 90]
+  --zoom <units>              half the view height of the other projections [default: 8]
+  --scene <file>              world (.mvxw), voxel stream (.mvxs) or MagicaVoxel (.vox) file
+                              to render [default: built in test pattern]
+  --heightmap                 render the built in 24x24 heightmap instead of the test pattern
+  --output <file>             image to write [default: render.png]
+  --format <format>           image format, png, jpeg, bmp, tga, tiff, pnm, ... [default: from the
+                              output extension]
+  --gbuffer <prefix>          also write the depth, normal, material and voxel coordinates of
+                              the first voxel in every pixel to <prefix>-depth.png and so on
+  --gbuffer-format <format>   png (16-bit) or float (.pfm) [default: png]
+  --sun <x,y,z>               direction towards the sun [default: -0.5,1,0.75]
+  --point-light <x,y,z>       light the scene with a point light instead of the sun
+  --no-shadows                skip the shadow rays
+  --no-ao                     skip the ambient occlusion
+  --pick <x,y>                print the voxel under pixel (x, y) instead of rendering
+  --traversal <algorithm>     dda, brickmap, octree, chebyshev, manhattan, mip or heightfield
+                              [default: dda]
+  --threads <count>           render threads [default: one per core]
+  -h, --help                  print this help
+";
+
+/// Camera projection, isometric and dimetric are orthographic with a fixed view direction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProjectionMode {
+    Perspective,
+    Orthographic,
+    Isometric,
+    Dimetric,
+}
+
+impl FromStr for ProjectionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "perspective" => Ok(ProjectionMode::Perspective),
+            "orthographic" => Ok(ProjectionMode::Orthographic),
+            "isometric" => Ok(ProjectionMode::Isometric),
+            "dimetric" => Ok(ProjectionMode::Dimetric),
+            _ => Err(format!("unknown projection '{}'", s)),
+        }
+    }
+}
+
+/// Voxel traversal the scene is rendered with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Traversal {
+    Dda,
+    BrickMap,
+    Octree,
+    Chebyshev,
+    Manhattan,
+    Mip,
+    /// 2D DDA over the columns of the scene, anything below the top voxel of a column is
+    /// treated as solid.
+    HeightField,
+}
+
+impl FromStr for Traversal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dda" => Ok(Traversal::Dda),
+            "brickmap" => Ok(Traversal::BrickMap),
+            "octree" => Ok(Traversal::Octree),
+            "chebyshev" => Ok(Traversal::Chebyshev),
+            "manhattan" => Ok(Traversal::Manhattan),
+            "mip" => Ok(Traversal::Mip),
+            "heightfield" => Ok(Traversal::HeightField),
+            _ => Err(format!("unknown traversal '{}'", s)),
+        }
+    }
+}
+
+pub struct Options {
+    pub width: u32,
+    pub height: u32,
+    pub camera: Vector3<f64>,
+    pub look_at: Vector3<f64>,
+    pub projection: ProjectionMode,
+    pub fov: f64,
+    pub zoom: f64,
+    pub scene: Option<PathBuf>,
+    pub heightmap: bool,
+    pub output: PathBuf,
+    pub format: ImageFormat,
+    pub gbuffer: Option<PathBuf>,
+    pub gbuffer_format: GBufferFormat,
+    pub light: Light,
+    pub shadows: bool,
+    pub ambient_occlusion: bool,
+    pub pick: Option<[u32; 2]>,
+    pub traversal: Traversal,
+    pub threads: usize,
+    pub help: bool,
+}
+
+impl Options {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        const ASPECT_RATIO: f64 = 16.0 / 9.0;
+
+        let mut width = 400;
+        let mut height = None;
+        let mut camera = Vector3::new(0.0, 0.0, 4.0);
+        let mut look_at = Vector3::new(0.0, 0.0, 3.0);
+        let mut projection = ProjectionMode::Perspective;
+        let mut fov = 90.0;
+        let mut zoom = 8.0;
+        let mut scene = None;
+        let mut output = PathBuf::from("render.png");
+        let mut format = None;
+        let mut gbuffer = None;
+        let mut gbuffer_format = GBufferFormat::Png;
+        let mut light = Light::Sun {
+            direction: Vector3::new(-0.5, 1.0, 0.75),
+        };
+        let mut shadows = true;
+        let mut ambient_occlusion = true;
+        let mut pick = None;
+        let mut traversal = Traversal::Dda;
+        let mut threads = None;
+        let mut heightmap = false;
+        let mut help = false;
+        while let Some(arg) = args.next() {
+            if arg == "-h" || arg == "--help" {
+                help = true;
+                continue;
+            }
+            if arg == "--heightmap" {
+                heightmap = true;
+                continue;
+            }
+            if arg == "--no-shadows" {
+                shadows = false;
+                continue;
+            }
+            if arg == "--no-ao" {
+                ambient_occlusion = false;
+                continue;
+            }
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "--width" => width = parse_number(&value()?)?,
+                "--height" => height = Some(parse_number(&value()?)?),
+                "--camera" => camera = parse_vector(&value()?)?,
+                "--look-at" => look_at = parse_vector(&value()?)?,
+                "--projection" => projection = value()?.parse()?,
+                "--fov" => fov = parse_number(&value()?)?,
+                "--zoom" => zoom = parse_number(&value()?)?,
+                "--scene" => scene = Some(PathBuf::from(value()?)),
+                "--output" => output = PathBuf::from(value()?),
+                "--format" => {
+                    let name = value()?;
+                    format = Some(
+                        ImageFormat::from_extension(&name)
+                            .ok_or_else(|| format!("unknown image format '{}'", name))?,
+                    );
+                }
+                "--gbuffer" => gbuffer = Some(PathBuf::from(value()?)),
+                "--gbuffer-format" => gbuffer_format = value()?.parse()?,
+                "--sun" => {
+                    light = Light::Sun {
+                        direction: parse_vector(&value()?)?,
+                    }
+                }
+                "--point-light" => {
+                    light = Light::Point {
+                        position: parse_vector(&value()?)?,
+                    }
+                }
+                "--pick" => pick = Some(parse_pixel(&value()?)?),
+                "--traversal" => traversal = value()?.parse()?,
+                "--threads" => threads = Some(parse_number(&value()?)?),
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+
+        let height = height.unwrap_or(((width as f64 / ASPECT_RATIO) as u32).max(1));
+        if width == 0 || height == 0 {
+            return Err("image size must be at least 1x1".to_string());
+        }
+        if !(fov > 0.0 && fov < 180.0) {
+            return Err("field of view must be between 0 and 180 degrees".to_string());
+        }
+        if !(zoom > 0.0 && f64::is_finite(zoom)) {
+            return Err("zoom must be a positive number".to_string());
+        }
+        if matches!(light, Light::Sun { direction } if direction == Vector3::new(0.0, 0.0, 0.0)) {
+            return Err("sun direction can't be zero".to_string());
+        }
+        if heightmap && scene.is_some() {
+            return Err("--heightmap and --scene can't be combined".to_string());
+        }
+        if camera == look_at {
+            return Err("camera and look at point must differ".to_string());
+        }
+        let format = match format {
+            Some(format) => format,
+            None => ImageFormat::from_path(&output).map_err(|_| {
+                format!(
+                    "can't tell the image format of {}, use --format",
+                    output.display()
+                )
+            })?,
+        };
+        if !format.can_write() {
+            return Err(format!("can't write {:?} images", format));
+        }
+        Ok(Self {
+            width,
+            height,
+            camera,
+            look_at,
+            projection,
+            fov,
+            zoom,
+            scene,
+            heightmap,
+            output,
+            format,
+            gbuffer,
+            gbuffer_format,
+            light,
+            shadows,
+            ambient_occlusion,
+            pick,
+            traversal,
+            threads: threads.unwrap_or_else(microvoxel_raycaster::render::default_threads),
+            help,
+        })
+    }
+}
+
+fn parse_number<T: FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number '{}'", value))
+}
+
+/// Parses "x,y".
+fn parse_pixel(value: &str) -> Result<[u32; 2], String> {
+    let components = value
+        .split(',')
+        .map(|c| parse_number(c.trim()))
+        .collect::<Result<Vec<u32>, String>>()?;
+    match components[..] {
+        [x, y] => Ok([x, y]),
+        _ => Err(format!("expected x,y but got '{}'", value)),
+    }
+}
+
+/// Parses "x,y,z".
+fn parse_vector(value: &str) -> Result<Vector3<f64>, String> {
+    let components = value
+        .split(',')
+        .map(|c| parse_number(c.trim()))
+        .collect::<Result<Vec<f64>, String>>()?;
+    match components[..] {
+        [x, y, z] => Ok(Vector3::new(x, y, z)),
+        _ => Err(format!("expected x,y,z but got '{}'", value)),
+    }
+}
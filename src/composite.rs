@@ -0,0 +1,60 @@
+use cgmath::Vector3;
+
+use crate::grid::VoxelGrid;
+use crate::interval::Interval;
+use crate::palette::Palette;
+use crate::ray::Ray;
+use crate::traversal::{Face, VoxelHit, VoxelTraversal};
+
+/// Accumulated alpha at which a ray counts as opaque and stops traversing.
+pub const OPAQUE: f64 = 0.99;
+/// How far in t past the far side of a translucent voxel the traversal picks up again, so it
+/// doesn't find the same voxel a second time.
+const CELL_EPSILON: f64 = 1e-9;
+
+/// Front to back compositing of the voxels along a ray. Translucent voxels are shaded with
+/// `shade` and blended in by their palette alpha, the traversal carries on behind them until
+/// the accumulated alpha reaches `OPAQUE` or the ray leaves the world and picks up
+/// `background`. `grid` supplies the voxel geometry the hits are in.
+pub fn composite(
+    world: &dyn VoxelTraversal,
+    grid: &VoxelGrid,
+    palette: &Palette,
+    ray: &Ray,
+    mut shade: impl FnMut(&VoxelHit) -> Vector3<f64>,
+    background: Vector3<f64>,
+) -> Vector3<f64> {
+    let mut color = Vector3::new(0.0, 0.0, 0.0);
+    let mut alpha = 0.0;
+    let mut t_min = 0.0;
+    let mut previous: Option<[i32; 3]> = None;
+    while alpha < OPAQUE {
+        let Some(mut hit) = world.hit(ray, Interval::new(t_min, f64::INFINITY)) else {
+            return color + (1.0 - alpha) * background;
+        };
+        if let (None, Some(previous)) = (hit.face, previous) {
+            // right behind the previous voxel, the traversal started inside of this one
+            hit.face = entered_from(previous, hit.cell);
+        }
+        let voxel_alpha = palette.alpha(hit.material);
+        color += (1.0 - alpha) * voxel_alpha * shade(&hit);
+        alpha += (1.0 - alpha) * voxel_alpha;
+
+        let Some(cell_t) = grid
+            .cell_bounds(hit.cell)
+            .hit(ray, Interval::new(hit.t, f64::INFINITY))
+        else {
+            break;
+        };
+        t_min = cell_t.max + CELL_EPSILON;
+        previous = Some(hit.cell);
+    }
+    color
+}
+
+/// Face `cell` is entered through coming from the neighbouring cell `previous`.
+fn entered_from(previous: [i32; 3], cell: [i32; 3]) -> Option<Face> {
+    (0..3)
+        .find(|&axis| cell[axis] != previous[axis])
+        .map(|axis| Face::entered(axis, cell[axis] - previous[axis]))
+}
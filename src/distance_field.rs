@@ -0,0 +1,285 @@
+use crate::grid::VoxelGrid;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::traversal::{grid_entry, skip_box, VoxelHit, VoxelTraversal};
+
+/// Distances are clamped, this bounds how far a single skip can jump and how much of the
+/// field has to be recomputed after an edit.
+pub const MAX_DISTANCE: u8 = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Metric {
+    /// Max of the per axis distances, the empty region around a cell is a cube.
+    Chebyshev,
+    /// Sum of the per axis distances, the empty region around a cell is an octahedron.
+    Manhattan,
+}
+
+impl Metric {
+    /// Neighbours looked at in the forward pass of the chamfer transform, as (dx, dy, dz).
+    /// These are the neighbours that come before a cell in memory order, the backward pass
+    /// uses the same offsets negated.
+    fn forward_neighbours(&self) -> Vec<[i32; 3]> {
+        match self {
+            Metric::Chebyshev => {
+                let mut neighbours = Vec::new();
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        for dx in -1..=1 {
+                            if dy < 0 || (dy == 0 && dz < 0) || (dy == 0 && dz == 0 && dx < 0) {
+                                neighbours.push([dx, dy, dz]);
+                            }
+                        }
+                    }
+                }
+                neighbours
+            }
+            Metric::Manhattan => vec![[-1, 0, 0], [0, 0, -1], [0, -1, 0]],
+        }
+    }
+    /// Half width of the largest cube of cells around a cell at `distance` that is empty.
+    fn empty_radius(&self, distance: u8) -> i32 {
+        let radius = distance as i32 - 1;
+        match self {
+            Metric::Chebyshev => radius,
+            Metric::Manhattan => radius / 3,
+        }
+    }
+}
+
+/// Per cell distance to the nearest solid voxel, 0 for solid voxels.
+pub struct DistanceField {
+    distances: Vec<u8>,
+    size: [usize; 3],
+    metric: Metric,
+}
+
+impl DistanceField {
+    pub fn new(grid: &VoxelGrid, metric: Metric) -> Self {
+        let size = grid.size();
+        let mut field = Self {
+            distances: Vec::new(),
+            size,
+            metric,
+        };
+        field.distances = field.transform(grid, [0; 3], size);
+        field
+    }
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+    /// Bytes used by the distances.
+    pub fn memory_footprint(&self) -> usize {
+        self.distances.len()
+    }
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        x + (z * self.size[0]) + (y * self.size[0] * self.size[2])
+    }
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.distances[self.index(x, y, z)]
+    }
+    /// Brings the field up to date after the voxel at (x, y, z) changed.
+    pub fn update(&mut self, grid: &VoxelGrid, x: usize, y: usize, z: usize) {
+        let reach = MAX_DISTANCE as usize;
+        let cell = [x, y, z];
+        if grid.get(x, y, z) != 0 {
+            // a new solid voxel can only bring distances down
+            let min: [usize; 3] = std::array::from_fn(|axis| cell[axis].saturating_sub(reach));
+            let max: [usize; 3] =
+                std::array::from_fn(|axis| (cell[axis] + reach + 1).min(self.size[axis]));
+            for ny in min[1]..max[1] {
+                for nz in min[2]..max[2] {
+                    for nx in min[0]..max[0] {
+                        let distance = self.distance_between(cell, [nx, ny, nz]);
+                        let index = self.index(nx, ny, nz);
+                        self.distances[index] = self.distances[index].min(distance);
+                    }
+                }
+            }
+        } else {
+            // a removed voxel changes the cells within reach, their distances only depend on
+            // the voxels within reach of them so recompute a region twice as big
+            let min: [usize; 3] = std::array::from_fn(|axis| cell[axis].saturating_sub(2 * reach));
+            let max: [usize; 3] =
+                std::array::from_fn(|axis| (cell[axis] + 2 * reach + 1).min(self.size[axis]));
+            let region = self.transform(grid, min, max);
+            let region_size: [usize; 3] = std::array::from_fn(|axis| max[axis] - min[axis]);
+            for ny in cell[1].saturating_sub(reach)..(cell[1] + reach + 1).min(self.size[1]) {
+                for nz in cell[2].saturating_sub(reach)..(cell[2] + reach + 1).min(self.size[2]) {
+                    for nx in cell[0].saturating_sub(reach)..(cell[0] + reach + 1).min(self.size[0])
+                    {
+                        let [rx, ry, rz] = [nx - min[0], ny - min[1], nz - min[2]];
+                        let index = self.index(nx, ny, nz);
+                        self.distances[index] =
+                            region[rx + rz * region_size[0] + ry * region_size[0] * region_size[2]];
+                    }
+                }
+            }
+        }
+    }
+    fn distance_between(&self, a: [usize; 3], b: [usize; 3]) -> u8 {
+        let d = [0, 1, 2].map(|axis| a[axis].abs_diff(b[axis]));
+        let distance = match self.metric {
+            Metric::Chebyshev => d[0].max(d[1]).max(d[2]),
+            Metric::Manhattan => d[0] + d[1] + d[2],
+        };
+        distance.min(MAX_DISTANCE as usize) as u8
+    }
+    /// Two pass chamfer distance transform of the region `min..max` of the grid, exact for
+    /// both metrics with unit weights.
+    fn transform(&self, grid: &VoxelGrid, min: [usize; 3], max: [usize; 3]) -> Vec<u8> {
+        let size: [usize; 3] = std::array::from_fn(|axis| max[axis] - min[axis]);
+        let index = |x: usize, y: usize, z: usize| x + (z * size[0]) + (y * size[0] * size[2]);
+        let mut distances = vec![MAX_DISTANCE; size.iter().product()];
+        for y in 0..size[1] {
+            for z in 0..size[2] {
+                for x in 0..size[0] {
+                    if grid.get(min[0] + x, min[1] + y, min[2] + z) != 0 {
+                        distances[index(x, y, z)] = 0;
+                    }
+                }
+            }
+        }
+        let forward = self.metric.forward_neighbours();
+        let backward: Vec<[i32; 3]> = forward.iter().map(|n| n.map(|d| -d)).collect();
+        let mut relax = |x: usize, y: usize, z: usize, neighbours: &[[i32; 3]]| {
+            let mut distance = distances[index(x, y, z)];
+            for [dx, dy, dz] in neighbours {
+                let (nx, ny, nz) = (x as i32 + dx, y as i32 + dy, z as i32 + dz);
+                if nx >= 0
+                    && ny >= 0
+                    && nz >= 0
+                    && (nx as usize) < size[0]
+                    && (ny as usize) < size[1]
+                    && (nz as usize) < size[2]
+                {
+                    let neighbour = distances[index(nx as usize, ny as usize, nz as usize)];
+                    distance = distance.min(neighbour.saturating_add(1));
+                }
+            }
+            distances[index(x, y, z)] = distance.min(MAX_DISTANCE);
+        };
+        for y in 0..size[1] {
+            for z in 0..size[2] {
+                for x in 0..size[0] {
+                    relax(x, y, z, &forward);
+                }
+            }
+        }
+        for y in (0..size[1]).rev() {
+            for z in (0..size[2]).rev() {
+                for x in (0..size[0]).rev() {
+                    relax(x, y, z, &backward);
+                }
+            }
+        }
+        distances
+    }
+}
+
+/// Voxel grid with a distance field that is kept up to date on every edit, traced by jumping
+/// over the empty space the distance field guarantees around a cell.
+pub struct DistanceFieldGrid {
+    grid: VoxelGrid,
+    field: DistanceField,
+}
+
+impl DistanceFieldGrid {
+    pub fn new(grid: VoxelGrid, metric: Metric) -> Self {
+        let field = DistanceField::new(&grid, metric);
+        Self { grid, field }
+    }
+    pub fn grid(&self) -> &VoxelGrid {
+        &self.grid
+    }
+    pub fn field(&self) -> &DistanceField {
+        &self.field
+    }
+    pub fn into_grid(self) -> VoxelGrid {
+        self.grid
+    }
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: u8) {
+        if self.grid.get(x, y, z) != value {
+            self.grid.set(x, y, z, value);
+            self.field.update(&self.grid, x, y, z);
+        }
+    }
+}
+
+impl VoxelTraversal for DistanceFieldGrid {
+    fn hit_counted(&self, ray: &Ray, ray_t: Interval, visited: &mut usize) -> Option<VoxelHit> {
+        let ray = Ray::new(
+            self.grid.world_to_grid(ray.origin),
+            ray.dir / self.grid.voxel_size(),
+        );
+        let size = self.grid.size();
+        let (ray_t, mut cell, mut face) = grid_entry(&ray, ray_t, size)?;
+        let mut t = ray_t.min;
+        while t <= ray_t.max && (0..3).all(|axis| cell[axis] >= 0 && cell[axis] < size[axis] as i32)
+        {
+            *visited += 1;
+            let [x, y, z] = cell.map(|c| c as usize);
+            let distance = self.field.get(x, y, z);
+            if distance == 0 {
+                return Some(VoxelHit {
+                    cell,
+                    t,
+                    face,
+                    material: self.grid.get(x, y, z),
+                });
+            }
+            let radius = self.field.metric.empty_radius(distance);
+            let next_face;
+            (t, cell, next_face) = skip_box(&ray, cell.map(|c| c - radius), 2 * radius + 1);
+            face = Some(next_face);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+    use rand::Rng;
+    use rand_pcg::Pcg64Mcg;
+
+    use super::*;
+
+    /// Random edits that keep only a handful of voxels around, so distances get long and
+    /// removals uncover voxels far away, checked against a field computed from scratch after
+    /// every edit.
+    fn check_updates(metric: Metric) {
+        let mut rng = Pcg64Mcg::new(11);
+        let mut grid = DistanceFieldGrid::new(
+            VoxelGrid::new(40, 4, 36, Vector3::new(0.0, 0.0, 0.0), 1.0),
+            metric,
+        );
+        let mut solid = Vec::new();
+        for _ in 0..200 {
+            if solid.len() < 6 || rng.gen_bool(0.5) {
+                let cell = [
+                    rng.gen_range(0..40),
+                    rng.gen_range(0..4),
+                    rng.gen_range(0..36),
+                ];
+                grid.set(cell[0], cell[1], cell[2], rng.gen_range(1..=255));
+                solid.push(cell);
+            } else {
+                let [x, y, z] = solid.swap_remove(rng.gen_range(0..solid.len()));
+                grid.set(x, y, z, 0);
+            }
+            let rebuilt = DistanceField::new(grid.grid(), metric);
+            assert_eq!(grid.field().distances, rebuilt.distances);
+        }
+    }
+
+    #[test]
+    fn chebyshev_update_matches_rebuild() {
+        check_updates(Metric::Chebyshev);
+    }
+
+    #[test]
+    fn manhattan_update_matches_rebuild() {
+        check_updates(Metric::Manhattan);
+    }
+}
@@ -0,0 +1,437 @@
+use std::collections::VecDeque;
+
+use cgmath::Vector3;
+
+use crate::grid::VoxelGrid;
+
+/// Cells `min..max` of a grid, `max` is exclusive.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CellBox {
+    pub min: [usize; 3],
+    pub max: [usize; 3],
+}
+
+impl CellBox {
+    pub fn new(min: [usize; 3], max: [usize; 3]) -> Self {
+        Self { min, max }
+    }
+    /// The single cell `cell`.
+    pub fn cell(cell: [usize; 3]) -> Self {
+        Self::new(cell, cell.map(|c| c + 1))
+    }
+    /// All cells of `grid`.
+    pub fn grid(grid: &VoxelGrid) -> Self {
+        Self::new([0; 3], grid.size())
+    }
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|axis| self.min[axis] >= self.max[axis])
+    }
+    pub fn contains(&self, cell: [usize; 3]) -> bool {
+        (0..3).all(|axis| self.min[axis] <= cell[axis] && cell[axis] < self.max[axis])
+    }
+    /// Smallest box containing both boxes.
+    pub fn union(&self, other: &CellBox) -> CellBox {
+        CellBox::new(
+            std::array::from_fn(|axis| self.min[axis].min(other.min[axis])),
+            std::array::from_fn(|axis| self.max[axis].max(other.max[axis])),
+        )
+    }
+    /// Cells inside of both boxes, can be empty.
+    pub fn intersection(&self, other: &CellBox) -> CellBox {
+        CellBox::new(
+            std::array::from_fn(|axis| self.min[axis].max(other.min[axis])),
+            std::array::from_fn(|axis| self.max[axis].min(other.max[axis])),
+        )
+    }
+    /// The box grown by `cells` on every side, not past 0.
+    pub fn grow(&self, cells: usize) -> CellBox {
+        CellBox::new(
+            self.min.map(|c| c.saturating_sub(cells)),
+            self.max.map(|c| c + cells),
+        )
+    }
+    /// Every cell of the box in grid index order, x fastest then z then y.
+    pub fn cells(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        (self.min[1]..self.max[1]).flat_map(move |y| {
+            (self.min[2]..self.max[2])
+                .flat_map(move |z| (self.min[0]..self.max[0]).map(move |x| [x, y, z]))
+        })
+    }
+}
+
+/// Writes voxels and keeps track of the box around the ones that actually changed.
+struct Changes<'a> {
+    grid: &'a mut VoxelGrid,
+    bounds: Option<CellBox>,
+}
+
+impl<'a> Changes<'a> {
+    fn new(grid: &'a mut VoxelGrid) -> Self {
+        Self { grid, bounds: None }
+    }
+    /// Sets a cell, cells outside of the grid are ignored.
+    fn set(&mut self, cell: [i32; 3], material: u8) {
+        if !self.grid.contains(cell[0], cell[1], cell[2]) {
+            return;
+        }
+        let [x, y, z] = cell.map(|c| c as usize);
+        if self.grid.get(x, y, z) == material {
+            return;
+        }
+        self.grid.set(x, y, z, material);
+        let changed = CellBox::cell([x, y, z]);
+        self.bounds = Some(match self.bounds {
+            Some(bounds) => bounds.union(&changed),
+            None => changed,
+        });
+    }
+    fn finish(self) -> Option<CellBox> {
+        self.bounds
+    }
+}
+
+/// Cells of the grid inside of the inclusive cell range `min..=max`, empty when the range is
+/// outside of the grid.
+fn clip(grid: &VoxelGrid, min: [i32; 3], max: [i32; 3]) -> CellBox {
+    let size = grid.size();
+    CellBox::new(
+        std::array::from_fn(|axis| min[axis].clamp(0, size[axis] as i32) as usize),
+        std::array::from_fn(|axis| (max[axis] + 1).clamp(0, size[axis] as i32) as usize),
+    )
+}
+
+fn cell_center(cell: [usize; 3]) -> Vector3<f64> {
+    Vector3::new(
+        cell[0] as f64 + 0.5,
+        cell[1] as f64 + 0.5,
+        cell[2] as f64 + 0.5,
+    )
+}
+
+/// Fills all cells from `min` to `max` inclusive. Like all edits the coordinates are cells of
+/// the grid, parts outside of the grid are clipped, and the box around the voxels that
+/// changed is returned, None when nothing changed.
+pub fn fill_box(
+    grid: &mut VoxelGrid,
+    min: [i32; 3],
+    max: [i32; 3],
+    material: u8,
+) -> Option<CellBox> {
+    let cells = clip(grid, min, max);
+    let mut changes = Changes::new(grid);
+    for cell in cells.cells() {
+        changes.set(cell.map(|c| c as i32), material);
+    }
+    changes.finish()
+}
+
+/// Only the one cell thick shell of the box from `min` to `max` inclusive, the inside is left
+/// alone.
+pub fn hollow_box(
+    grid: &mut VoxelGrid,
+    min: [i32; 3],
+    max: [i32; 3],
+    material: u8,
+) -> Option<CellBox> {
+    let cells = clip(grid, min, max);
+    let mut changes = Changes::new(grid);
+    for cell in cells.cells() {
+        let cell = cell.map(|c| c as i32);
+        if (0..3).any(|axis| cell[axis] == min[axis] || cell[axis] == max[axis]) {
+            changes.set(cell, material);
+        }
+    }
+    changes.finish()
+}
+
+/// Fills the cells whose centers are inside of the axis aligned ellipsoid around `center`
+/// with half axes `radii`, in grid space where a cell is one unit.
+pub fn ellipsoid(
+    grid: &mut VoxelGrid,
+    center: Vector3<f64>,
+    radii: Vector3<f64>,
+    material: u8,
+) -> Option<CellBox> {
+    let cells = clip(
+        grid,
+        std::array::from_fn(|axis| (center[axis] - radii[axis]).floor() as i32),
+        std::array::from_fn(|axis| (center[axis] + radii[axis]).ceil() as i32),
+    );
+    let mut changes = Changes::new(grid);
+    for cell in cells.cells() {
+        let offset = cell_center(cell) - center;
+        let distance: f64 = (0..3)
+            .map(|axis| (offset[axis] / radii[axis]).powi(2))
+            .sum();
+        if distance <= 1.0 {
+            changes.set(cell.map(|c| c as i32), material);
+        }
+    }
+    changes.finish()
+}
+
+pub fn sphere(
+    grid: &mut VoxelGrid,
+    center: Vector3<f64>,
+    radius: f64,
+    material: u8,
+) -> Option<CellBox> {
+    ellipsoid(grid, center, Vector3::new(radius, radius, radius), material)
+}
+
+/// Fills the cells whose centers are inside of the cylinder along `axis` (0, 1 or 2) whose
+/// bottom cap is centered on `base`, `length` long with `radius`.
+pub fn cylinder(
+    grid: &mut VoxelGrid,
+    base: Vector3<f64>,
+    axis: usize,
+    length: f64,
+    radius: f64,
+    material: u8,
+) -> Option<CellBox> {
+    let mut min = base - Vector3::new(radius, radius, radius);
+    let mut max = base + Vector3::new(radius, radius, radius);
+    min[axis] = base[axis];
+    max[axis] = base[axis] + length;
+    let cells = clip(
+        grid,
+        std::array::from_fn(|i| min[i].floor() as i32),
+        std::array::from_fn(|i| max[i].ceil() as i32),
+    );
+    let mut changes = Changes::new(grid);
+    for cell in cells.cells() {
+        let offset = cell_center(cell) - base;
+        let along = offset[axis];
+        let across: f64 = (0..3)
+            .filter(|&i| i != axis)
+            .map(|i| offset[i].powi(2))
+            .sum();
+        if (0.0..=length).contains(&along) && across <= radius * radius {
+            changes.set(cell.map(|c| c as i32), material);
+        }
+    }
+    changes.finish()
+}
+
+/// 3D Bresenham line from cell `from` to cell `to`, both ends included. Steps along the axis
+/// with the largest distance and keeps an error term for each of the other two.
+pub fn line(grid: &mut VoxelGrid, from: [i32; 3], to: [i32; 3], material: u8) -> Option<CellBox> {
+    let delta: [i32; 3] = std::array::from_fn(|axis| (to[axis] - from[axis]).abs());
+    let step: [i32; 3] = std::array::from_fn(|axis| (to[axis] - from[axis]).signum());
+    let main = (0..3).max_by_key(|&axis| delta[axis]).unwrap();
+    let mut errors: [i32; 3] = std::array::from_fn(|axis| 2 * delta[axis] - delta[main]);
+    let mut cell = from;
+    let mut changes = Changes::new(grid);
+    changes.set(cell, material);
+    for _ in 0..delta[main] {
+        for axis in (0..3).filter(|&axis| axis != main) {
+            if errors[axis] > 0 {
+                cell[axis] += step[axis];
+                errors[axis] -= 2 * delta[main];
+            }
+            errors[axis] += 2 * delta[axis];
+        }
+        cell[main] += step[main];
+        changes.set(cell, material);
+    }
+    changes.finish()
+}
+
+/// Replaces the region of cells connected to `start` through faces that have the material of
+/// `start` with `material`, without leaving `bounds`. Filling empty space works the same, so
+/// `bounds` keeps a fill of open air from running through the whole grid.
+pub fn flood_fill(
+    grid: &mut VoxelGrid,
+    start: [usize; 3],
+    bounds: CellBox,
+    material: u8,
+) -> Option<CellBox> {
+    let bounds = bounds.intersection(&CellBox::grid(grid));
+    if !bounds.contains(start) {
+        return None;
+    }
+    let target = grid.get(start[0], start[1], start[2]);
+    if target == material {
+        return None;
+    }
+    let mut changes = Changes::new(grid);
+    let mut queue = VecDeque::from([start]);
+    changes.set(start.map(|c| c as i32), material);
+    while let Some(cell) = queue.pop_front() {
+        for axis in 0..3 {
+            for neighbour in [cell[axis].wrapping_sub(1), cell[axis] + 1] {
+                let mut next = cell;
+                next[axis] = neighbour;
+                // the wrapped around neighbour of 0 is outside of the bounds as well
+                if bounds.contains(next) && changes.grid.get(next[0], next[1], next[2]) == target {
+                    changes.set(next.map(|c| c as i32), material);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    changes.finish()
+}
+
+/// Replaces every `from` voxel inside of `bounds` with `to`.
+pub fn replace(grid: &mut VoxelGrid, bounds: CellBox, from: u8, to: u8) -> Option<CellBox> {
+    let bounds = bounds.intersection(&CellBox::grid(grid));
+    let mut changes = Changes::new(grid);
+    for [x, y, z] in bounds.cells() {
+        if changes.grid.get(x, y, z) == from {
+            changes.set([x as i32, y as i32, z as i32], to);
+        }
+    }
+    changes.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> VoxelGrid {
+        VoxelGrid::new(16, 12, 10, Vector3::new(0.0, 0.0, 0.0), 1.0)
+    }
+
+    /// Box around every solid voxel, None when the grid is empty.
+    fn solid_bounds(grid: &VoxelGrid) -> Option<CellBox> {
+        CellBox::grid(grid)
+            .cells()
+            .filter(|&[x, y, z]| grid.get(x, y, z) != 0)
+            .map(CellBox::cell)
+            .reduce(|a, b| a.union(&b))
+    }
+
+    fn count(grid: &VoxelGrid, material: u8) -> usize {
+        CellBox::grid(grid)
+            .cells()
+            .filter(|&[x, y, z]| grid.get(x, y, z) == material)
+            .count()
+    }
+
+    #[test]
+    fn fill_box_returns_changed_bounds() {
+        let mut grid = grid();
+        let changed = fill_box(&mut grid, [2, 3, 4], [5, 3, 6], 1);
+        assert_eq!(changed, Some(CellBox::new([2, 3, 4], [6, 4, 7])));
+        assert_eq!(changed, solid_bounds(&grid));
+        assert_eq!(count(&grid, 1), 4 * 3);
+        // nothing left to change
+        assert_eq!(fill_box(&mut grid, [2, 3, 4], [5, 3, 6], 1), None);
+        // only the cells that actually changed count
+        assert_eq!(
+            fill_box(&mut grid, [0, 3, 4], [5, 3, 6], 1),
+            Some(CellBox::new([0, 3, 4], [2, 4, 7]))
+        );
+    }
+
+    #[test]
+    fn edits_are_clipped_to_the_grid() {
+        let mut grid = grid();
+        let changed = fill_box(&mut grid, [-3, -3, -3], [1, 20, 1], 2);
+        assert_eq!(changed, Some(CellBox::new([0, 0, 0], [2, 12, 2])));
+        assert_eq!(count(&grid, 2), 2 * 12 * 2);
+
+        let mut grid = self::grid();
+        let changed = sphere(&mut grid, Vector3::new(16.0, 6.0, 5.0), 3.0, 3);
+        assert_eq!(changed, solid_bounds(&grid));
+        assert_eq!(changed.unwrap().max[0], 16);
+
+        let mut grid = self::grid();
+        assert_eq!(fill_box(&mut grid, [20, 0, 0], [30, 5, 5], 1), None);
+        assert_eq!(hollow_box(&mut grid, [-9, -9, -9], [-1, 20, 20], 1), None);
+        assert_eq!(count(&grid, 0), 16 * 12 * 10);
+    }
+
+    #[test]
+    fn hollow_box_leaves_the_inside() {
+        let mut grid = grid();
+        let changed = hollow_box(&mut grid, [1, 1, 1], [5, 5, 5], 1);
+        assert_eq!(changed, Some(CellBox::new([1, 1, 1], [6, 6, 6])));
+        assert_eq!(count(&grid, 1), 5 * 5 * 5 - 3 * 3 * 3);
+        assert_eq!(grid.get(3, 3, 3), 0);
+    }
+
+    #[test]
+    fn sphere_and_cylinder_bounds() {
+        let mut grid = grid();
+        let changed = sphere(&mut grid, Vector3::new(8.0, 6.0, 5.0), 2.0, 1);
+        assert_eq!(changed, Some(CellBox::new([6, 4, 3], [10, 8, 7])));
+        assert_eq!(changed, solid_bounds(&grid));
+
+        let mut grid = self::grid();
+        let changed = cylinder(&mut grid, Vector3::new(8.0, 2.0, 5.0), 1, 6.0, 1.5, 1);
+        assert_eq!(changed, Some(CellBox::new([7, 2, 4], [9, 8, 6])));
+        assert_eq!(changed, solid_bounds(&grid));
+    }
+
+    #[test]
+    fn line_includes_both_ends() {
+        let mut grid = grid();
+        let (from, to) = ([1, 2, 3], [14, 7, 4]);
+        let changed = line(&mut grid, from, to, 1);
+        assert_eq!(changed, Some(CellBox::new([1, 2, 3], [15, 8, 5])));
+        assert_eq!(grid.get(1, 2, 3), 1);
+        assert_eq!(grid.get(14, 7, 4), 1);
+        // one cell per step along the longest axis
+        assert_eq!(count(&grid, 1), 14);
+
+        // the other way around and leaving the grid
+        let mut grid = self::grid();
+        let changed = line(&mut grid, [20, 9, 2], [0, 1, 2], 1);
+        assert_eq!(grid.get(0, 1, 2), 1);
+        assert_eq!(changed, solid_bounds(&grid));
+        assert_eq!(changed.unwrap().min, [0, 1, 2]);
+
+        let mut grid = self::grid();
+        assert_eq!(
+            line(&mut grid, [4, 4, 4], [4, 4, 4], 1),
+            Some(CellBox::cell([4, 4, 4]))
+        );
+    }
+
+    #[test]
+    fn flood_fill_stays_inside_bounds() {
+        // a room with a door in its wall, open air on both sides
+        let mut grid = grid();
+        hollow_box(&mut grid, [2, 2, 2], [8, 8, 8], 1);
+        fill_box(&mut grid, [8, 4, 4], [8, 5, 5], 0);
+
+        let inside = CellBox::new([3, 3, 3], [8, 8, 8]);
+        let changed = flood_fill(&mut grid, [5, 5, 5], inside, 2);
+        assert_eq!(changed, Some(inside));
+        assert_eq!(count(&grid, 2), 5 * 5 * 5);
+
+        // through the door the air connects to everything, the bounds stop it
+        let mut grid = self::grid();
+        hollow_box(&mut grid, [2, 2, 2], [8, 8, 8], 1);
+        fill_box(&mut grid, [8, 4, 4], [8, 5, 5], 0);
+        let bounds = CellBox::new([0, 0, 0], [11, 9, 9]);
+        let changed = flood_fill(&mut grid, [5, 5, 5], bounds, 3).unwrap();
+        assert!(bounds.contains(changed.min) && bounds.contains(changed.max.map(|c| c - 1)));
+        assert_eq!(changed.max, [11, 9, 9]);
+        for [x, y, z] in CellBox::grid(&grid).cells() {
+            if grid.get(x, y, z) == 3 {
+                assert!(bounds.contains([x, y, z]));
+            }
+        }
+        // the solid walls and everything outside of the bounds are left alone
+        assert_eq!(grid.get(2, 5, 5), 1);
+        assert_eq!(grid.get(11, 5, 5), 0);
+        assert_eq!(grid.get(5, 10, 5), 0);
+
+        // the start has to be inside of the bounds and the grid
+        assert_eq!(flood_fill(&mut grid, [12, 5, 5], bounds, 4), None);
+        assert_eq!(flood_fill(&mut grid, [5, 5, 5], bounds, 3), None);
+    }
+
+    #[test]
+    fn replace_only_touches_bounds() {
+        let mut grid = grid();
+        fill_box(&mut grid, [0, 0, 0], [15, 0, 9], 1);
+        let changed = replace(&mut grid, CellBox::new([4, 0, 4], [8, 5, 20]), 1, 2);
+        assert_eq!(changed, Some(CellBox::new([4, 0, 4], [8, 1, 10])));
+        assert_eq!(count(&grid, 2), 4 * 6);
+        assert_eq!(count(&grid, 1), 16 * 10 - 4 * 6);
+    }
+}
@@ -0,0 +1,195 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use cgmath::Vector3;
+use image::{ImageBuffer, ImageResult, Luma, Rgb};
+
+use crate::camera::Camera;
+use crate::grid::VoxelGrid;
+use crate::ray::Ray;
+use crate::traversal::VoxelHit;
+
+/// How the G-buffer images are stored.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GBufferFormat {
+    /// 16-bit PNGs, depth is scaled to the far side of the scene.
+    Png,
+    /// Portable float maps (.pfm) with the values as they are.
+    Float,
+}
+
+impl FromStr for GBufferFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "png" => Ok(GBufferFormat::Png),
+            "float" => Ok(GBufferFormat::Float),
+            _ => Err(format!("unknown G-buffer format '{}'", s)),
+        }
+    }
+}
+
+/// The first voxel along the ray of a pixel.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sample {
+    /// Distance in front of the camera along the view direction.
+    pub depth: f64,
+    /// World space normal of the face the ray entered through, zero when the ray starts inside
+    /// of the voxel.
+    pub normal: Vector3<f64>,
+    pub material: u8,
+    pub cell: [i32; 3],
+}
+
+impl Sample {
+    pub fn new(camera: &Camera, ray: &Ray, hit: &VoxelHit) -> Self {
+        Self {
+            depth: camera.depth(ray.at(hit.t)),
+            normal: hit
+                .face
+                .map_or(Vector3::new(0.0, 0.0, 0.0), |face| face.normal()),
+            material: hit.material,
+            cell: hit.cell,
+        }
+    }
+}
+
+/// Per pixel hit information next to the color image: linear depth, world normal, material
+/// and voxel coordinates, for baking sprites and debugging.
+pub struct GBuffer {
+    width: u32,
+    height: u32,
+    samples: Vec<Option<Sample>>,
+}
+
+impl GBuffer {
+    /// `samples` holds the pixels row by row, None where the ray hit nothing.
+    pub fn new(width: u32, height: u32, samples: Vec<Option<Sample>>) -> Self {
+        assert_eq!(samples.len(), (width * height) as usize);
+        Self {
+            width,
+            height,
+            samples,
+        }
+    }
+    pub fn sample(&self, x: u32, y: u32) -> Option<Sample> {
+        self.samples[(x + y * self.width) as usize]
+    }
+    /// Writes `<prefix>-depth`, `-normal`, `-material` and `-voxel` next to `prefix`.
+    ///
+    /// As PNGs the depth is a fraction of `far` with 65535 for nothing hit, normals map -1..1
+    /// to 0..65535, and materials and voxel coordinates are stored as they are with 0 and
+    /// 65535 for nothing hit. As float maps nothing hit is an infinite depth, a zero normal,
+    /// material 0 and voxel -1.
+    pub fn save(&self, prefix: &Path, format: GBufferFormat, far: f64) -> ImageResult<()> {
+        match format {
+            GBufferFormat::Png => self.save_png(prefix, far),
+            GBufferFormat::Float => self.save_float(prefix),
+        }
+    }
+    fn save_png(&self, prefix: &Path, far: f64) -> ImageResult<()> {
+        let depth = ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            Luma([self.sample(x, y).map_or(u16::MAX, |sample| {
+                ((sample.depth / far).clamp(0.0, 1.0) * (u16::MAX - 1) as f64).round() as u16
+            })])
+        });
+        depth.save(file_name(prefix, "depth", "png"))?;
+        let normal = ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            Rgb(self.sample(x, y).map_or([0; 3], |sample| {
+                std::array::from_fn(|axis| {
+                    ((sample.normal[axis] + 1.0) / 2.0 * u16::MAX as f64).round() as u16
+                })
+            }))
+        });
+        normal.save(file_name(prefix, "normal", "png"))?;
+        let material = ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            Luma([self.sample(x, y).map_or(0, |sample| sample.material as u16)])
+        });
+        material.save(file_name(prefix, "material", "png"))?;
+        let voxel = ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            Rgb(self.sample(x, y).map_or([u16::MAX; 3], |sample| {
+                sample.cell.map(|c| c.clamp(0, u16::MAX as i32) as u16)
+            }))
+        });
+        voxel.save(file_name(prefix, "voxel", "png"))?;
+        Ok(())
+    }
+    fn save_float(&self, prefix: &Path) -> ImageResult<()> {
+        self.write_pfm(&file_name(prefix, "depth", "pfm"), |sample| {
+            vec![sample.map_or(f32::INFINITY, |sample| sample.depth as f32)]
+        })?;
+        self.write_pfm(&file_name(prefix, "normal", "pfm"), |sample| {
+            let normal = sample.map_or(Vector3::new(0.0, 0.0, 0.0), |sample| sample.normal);
+            vec![normal.x as f32, normal.y as f32, normal.z as f32]
+        })?;
+        self.write_pfm(&file_name(prefix, "material", "pfm"), |sample| {
+            vec![sample.map_or(0.0, |sample| sample.material as f32)]
+        })?;
+        self.write_pfm(&file_name(prefix, "voxel", "pfm"), |sample| {
+            sample.map_or(vec![-1.0; 3], |sample| {
+                sample.cell.iter().map(|&c| c as f32).collect()
+            })
+        })?;
+        Ok(())
+    }
+    /// Portable float map with one (Pf) or three (PF) channels from `channels`, little endian
+    /// with the bottom row first.
+    fn write_pfm(
+        &self,
+        path: &Path,
+        channels: impl Fn(Option<Sample>) -> Vec<f32>,
+    ) -> ImageResult<()> {
+        let count = channels(None).len();
+        let mut file = BufWriter::new(File::create(path)?);
+        let kind = if count == 1 { "Pf" } else { "PF" };
+        write!(file, "{}\n{} {}\n-1.0\n", kind, self.width, self.height)?;
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                for value in channels(self.sample(x, y)) {
+                    file.write_all(&value.to_le_bytes())?;
+                }
+            }
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// `<prefix>-<channel>.<extension>` in the directory of `prefix`.
+fn file_name(prefix: &Path, channel: &str, extension: &str) -> PathBuf {
+    let stem = prefix
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+    prefix.with_file_name(format!("{}-{}.{}", stem, channel, extension))
+}
+
+/// Depth of the corner of the grid farthest in front of the camera, what the PNG depth is
+/// scaled to.
+pub fn far_depth(camera: &Camera, grid: &VoxelGrid) -> f64 {
+    let bounds = grid.bounds();
+    (0..8)
+        .map(|corner| {
+            let corner = Vector3::new(
+                if corner & 1 == 0 {
+                    bounds.min.x
+                } else {
+                    bounds.max.x
+                },
+                if corner & 2 == 0 {
+                    bounds.min.y
+                } else {
+                    bounds.max.y
+                },
+                if corner & 4 == 0 {
+                    bounds.min.z
+                } else {
+                    bounds.max.z
+                },
+            );
+            camera.depth(corner)
+        })
+        .fold(0.0, f64::max)
+}
@@ -0,0 +1,138 @@
+use cgmath::Vector3;
+
+use crate::aabb::Aabb;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::traversal::{VoxelHit, VoxelRayIter, VoxelTraversal};
+
+/// Dense voxel grid of material indices, 0 is empty space.
+/// Voxels are laid out like the lattice: x first, then z, then y.
+#[derive(Clone)]
+pub struct VoxelGrid {
+    data: Vec<u8>,
+    size_x: usize,
+    size_y: usize,
+    size_z: usize,
+    origin: Vector3<f64>,
+    voxel_size: f64,
+}
+
+impl VoxelGrid {
+    pub fn new(
+        size_x: usize,
+        size_y: usize,
+        size_z: usize,
+        origin: Vector3<f64>,
+        voxel_size: f64,
+    ) -> Self {
+        Self {
+            data: vec![0; size_x * size_y * size_z],
+            size_x,
+            size_y,
+            size_z,
+            origin,
+            voxel_size,
+        }
+    }
+    pub fn size(&self) -> [usize; 3] {
+        [self.size_x, self.size_y, self.size_z]
+    }
+    pub fn origin(&self) -> Vector3<f64> {
+        self.origin
+    }
+    pub fn voxel_size(&self) -> f64 {
+        self.voxel_size
+    }
+    /// Bytes used by the voxels.
+    pub fn memory_footprint(&self) -> usize {
+        self.data.len()
+    }
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        x + (z * self.size_x) + (y * self.size_x * self.size_z)
+    }
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.data[self.index(x, y, z)]
+    }
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: u8) {
+        let index = self.index(x, y, z);
+        self.data[index] = value;
+    }
+    pub fn contains(&self, x: i32, y: i32, z: i32) -> bool {
+        x >= 0
+            && y >= 0
+            && z >= 0
+            && (x as usize) < self.size_x
+            && (y as usize) < self.size_y
+            && (z as usize) < self.size_z
+    }
+    /// Bounds checked lookup with signed cell coordinates, None when outside of the grid.
+    pub fn get_checked(&self, x: i32, y: i32, z: i32) -> Option<u8> {
+        if self.contains(x, y, z) {
+            Some(self.get(x as usize, y as usize, z as usize))
+        } else {
+            None
+        }
+    }
+    /// Resizes the grid keeping the voxels that are inside both the old and the new size.
+    pub fn resize(&mut self, size_x: usize, size_y: usize, size_z: usize) {
+        let mut data = vec![0; size_x * size_y * size_z];
+        for y in 0..self.size_y.min(size_y) {
+            for z in 0..self.size_z.min(size_z) {
+                for x in 0..self.size_x.min(size_x) {
+                    data[x + (z * size_x) + (y * size_x * size_z)] = self.get(x, y, z);
+                }
+            }
+        }
+        self.data = data;
+        self.size_x = size_x;
+        self.size_y = size_y;
+        self.size_z = size_z;
+    }
+    /// World space position to continuous grid space, one unit per voxel.
+    pub fn world_to_grid(&self, position: Vector3<f64>) -> Vector3<f64> {
+        (position - self.origin) / self.voxel_size
+    }
+    pub fn grid_to_world(&self, position: Vector3<f64>) -> Vector3<f64> {
+        self.origin + position * self.voxel_size
+    }
+    /// World space bounding box of the grid.
+    pub fn bounds(&self) -> Aabb {
+        Aabb::new(
+            self.origin,
+            self.grid_to_world(Vector3::new(
+                self.size_x as f64,
+                self.size_y as f64,
+                self.size_z as f64,
+            )),
+        )
+    }
+    /// World space bounding box of a single cell.
+    pub fn cell_bounds(&self, cell: [i32; 3]) -> Aabb {
+        let min = self.grid_to_world(Vector3::new(cell[0] as f64, cell[1] as f64, cell[2] as f64));
+        Aabb::new(min, min + Vector3::new(1.0, 1.0, 1.0) * self.voxel_size)
+    }
+    /// Cells of the grid along a world space ray, t values of the visited cells are in world
+    /// space as well. Only cells inside of the grid are visited.
+    pub fn traverse(&self, ray: &Ray, ray_t: Interval) -> VoxelRayIter {
+        let ray = Ray::new(self.world_to_grid(ray.origin), ray.dir / self.voxel_size);
+        VoxelRayIter::clipped(&ray, ray_t, self.size())
+    }
+}
+
+impl VoxelTraversal for VoxelGrid {
+    fn hit_counted(&self, ray: &Ray, ray_t: Interval, visited: &mut usize) -> Option<VoxelHit> {
+        self.traverse(ray, ray_t).find_map(|step| {
+            *visited += 1;
+            let [x, y, z] = step.cell;
+            match self.get(x as usize, y as usize, z as usize) {
+                0 => None,
+                material => Some(VoxelHit {
+                    cell: step.cell,
+                    t: step.t_enter,
+                    face: step.face,
+                    material,
+                }),
+            }
+        })
+    }
+}
@@ -0,0 +1,134 @@
+use cgmath::Vector3;
+
+use crate::grid::VoxelGrid;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::traversal::{grid_entry, Face, VoxelHit, VoxelRayIter, VoxelTraversal};
+
+/// 2.5D terrain: one height and one material per column over x and z, a column is solid from
+/// the bottom of the field up to its height. Traced with a 2D DDA over the columns that only
+/// compares the height of the ray against the column, no matter how tall the field is.
+pub struct HeightField {
+    heights: Vec<u16>,
+    materials: Vec<u8>,
+    size: [usize; 3],
+    origin: Vector3<f64>,
+    voxel_size: f64,
+}
+
+impl HeightField {
+    /// Empty field of `size_x` by `size_z` columns up to `size_y` cells high.
+    pub fn new(
+        size_x: usize,
+        size_y: usize,
+        size_z: usize,
+        origin: Vector3<f64>,
+        voxel_size: f64,
+    ) -> Self {
+        Self {
+            heights: vec![0; size_x * size_z],
+            materials: vec![0; size_x * size_z],
+            size: [size_x, size_y, size_z],
+            origin,
+            voxel_size,
+        }
+    }
+    /// Columns from the top voxel of every column of the grid, anything below the top voxel
+    /// is filled in with its material.
+    pub fn from_grid(grid: &VoxelGrid) -> Self {
+        let [size_x, size_y, size_z] = grid.size();
+        let mut field = Self::new(size_x, size_y, size_z, grid.origin(), grid.voxel_size());
+        for z in 0..size_z {
+            for x in 0..size_x {
+                if let Some(y) = (0..size_y).rev().find(|&y| grid.get(x, y, z) != 0) {
+                    field.set(x, z, y + 1, grid.get(x, y, z));
+                }
+            }
+        }
+        field
+    }
+    pub fn to_grid(&self) -> VoxelGrid {
+        let [size_x, size_y, size_z] = self.size;
+        let mut grid = VoxelGrid::new(size_x, size_y, size_z, self.origin, self.voxel_size);
+        for z in 0..size_z {
+            for x in 0..size_x {
+                for y in 0..self.height(x, z) {
+                    grid.set(x, y, z, self.material(x, z));
+                }
+            }
+        }
+        grid
+    }
+    pub fn size(&self) -> [usize; 3] {
+        self.size
+    }
+    /// Bytes used by the heights and materials.
+    pub fn memory_footprint(&self) -> usize {
+        self.heights.len() * std::mem::size_of::<u16>() + self.materials.len()
+    }
+    fn index(&self, x: usize, z: usize) -> usize {
+        x + z * self.size[0]
+    }
+    pub fn height(&self, x: usize, z: usize) -> usize {
+        self.heights[self.index(x, z)] as usize
+    }
+    pub fn material(&self, x: usize, z: usize) -> u8 {
+        self.materials[self.index(x, z)]
+    }
+    /// Sets column (x, z) to `height` cells of `material`, the height is clamped to the
+    /// height of the field.
+    pub fn set(&mut self, x: usize, z: usize, height: usize, material: u8) {
+        let index = self.index(x, z);
+        self.heights[index] = height.min(self.size[1]) as u16;
+        self.materials[index] = material;
+    }
+}
+
+impl VoxelTraversal for HeightField {
+    /// Steps over the columns the ray crosses and hits a column when the ray is below its top
+    /// on the way in, or drops below it before leaving.
+    fn hit_counted(&self, ray: &Ray, ray_t: Interval, visited: &mut usize) -> Option<VoxelHit> {
+        let ray = Ray::new(
+            (ray.origin - self.origin) / self.voxel_size,
+            ray.dir / self.voxel_size,
+        );
+        let (ray_t, _, entry_face) = grid_entry(&ray, ray_t, self.size)?;
+        // same ray flattened onto the ground, x and z and so t are unchanged
+        let flat_ray = Ray::new(
+            Vector3::new(ray.origin.x, 0.5, ray.origin.z),
+            Vector3::new(ray.dir.x, 0.0, ray.dir.z),
+        );
+        let flat_size = [self.size[0], 1, self.size[2]];
+        let mut first = true;
+        for step in VoxelRayIter::clipped(&flat_ray, ray_t, flat_size) {
+            *visited += 1;
+            let [x, _, z] = step.cell;
+            let (x, z) = (x as usize, z as usize);
+            let face = if first { entry_face } else { step.face };
+            first = false;
+            let height = self.height(x, z) as f64;
+            if height == 0.0 {
+                continue;
+            }
+            let y_enter = ray.at(step.t_enter).y;
+            let y_exit = ray.at(step.t_exit).y;
+            let (t, y, face) = if y_enter < height {
+                (step.t_enter, y_enter, face)
+            } else if y_exit < height {
+                // comes down through the top
+                let t = (height - ray.origin.y) / ray.dir.y;
+                (t, height - 1.0, Some(Face::PosY))
+            } else {
+                continue;
+            };
+            let y = (y.floor() as i32).clamp(0, height as i32 - 1);
+            return Some(VoxelHit {
+                cell: [x as i32, y, z as i32],
+                t,
+                face,
+                material: self.material(x, z),
+            });
+        }
+        None
+    }
+}
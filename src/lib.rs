@@ -0,0 +1,24 @@
+pub mod aabb;
+pub mod ao;
+pub mod brickmap;
+pub mod camera;
+pub mod composite;
+pub mod distance_field;
+pub mod edit;
+pub mod gbuffer;
+pub mod grid;
+pub mod heightfield;
+pub mod interval;
+pub mod light;
+pub mod mip_pyramid;
+pub mod morton;
+pub mod octree;
+pub mod octree_builder;
+pub mod palette;
+pub mod pick;
+pub mod ray;
+pub mod render;
+pub mod traversal;
+pub mod vox;
+pub mod voxel_stream;
+pub mod world_file;
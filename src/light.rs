@@ -0,0 +1,56 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::traversal::VoxelTraversal;
+
+/// Distance in world units shadow rays start off the surface they leave, so they don't hit the
+/// voxel they start on.
+pub const SHADOW_OFFSET: f64 = 1e-6;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Light {
+    /// Light from infinitely far away, `direction` points towards the sun.
+    Sun {
+        direction: Vector3<f64>,
+    },
+    Point {
+        position: Vector3<f64>,
+    },
+}
+
+impl Light {
+    /// Unit vector from `point` towards the light.
+    pub fn direction_from(&self, point: Vector3<f64>) -> Vector3<f64> {
+        match self {
+            Light::Sun { direction } => direction.normalize(),
+            Light::Point { position } => (position - point).normalize(),
+        }
+    }
+    /// Shadow ray from `point` on a surface with `normal` towards the light, with the interval
+    /// the ray has to be free over for the light to be visible.
+    pub fn shadow_ray(&self, point: Vector3<f64>, normal: Vector3<f64>) -> (Ray, Interval) {
+        let origin = point + normal * SHADOW_OFFSET;
+        match self {
+            Light::Sun { direction } => (
+                Ray::new(origin, *direction),
+                Interval::new(0.0, f64::INFINITY),
+            ),
+            // t = 1 is the light itself
+            Light::Point { position } => {
+                (Ray::new(origin, position - origin), Interval::new(0.0, 1.0))
+            }
+        }
+    }
+    /// Whether the light reaches `point` on a surface with `normal`, traced with the same
+    /// traversal as the primary rays.
+    pub fn visible(
+        &self,
+        world: &dyn VoxelTraversal,
+        point: Vector3<f64>,
+        normal: Vector3<f64>,
+    ) -> bool {
+        let (ray, ray_t) = self.shadow_ray(point, normal);
+        world.hit(&ray, ray_t).is_none()
+    }
+}
@@ -1,10 +1,39 @@
-use cgmath::InnerSpace;
-use cgmath::Vector3;
-use image::{ImageBuffer, Rgb, RgbImage};
-use rand::Rng;
-use rand_pcg::Pcg64Mcg;
-
-/*const WORLD: [[u8; 24]; 24] =
+mod cli;
+
+use std::error::Error;
+use std::path::Path;
+use std::process;
+
+use cgmath::{InnerSpace, Vector3, VectorSpace};
+use image::{Rgb, RgbImage};
+use microvoxel_raycaster::ao;
+use microvoxel_raycaster::brickmap::BrickMap;
+use microvoxel_raycaster::camera::{
+    axonometric_position, Camera, Projection, DIMETRIC_ELEVATION, ISOMETRIC_ELEVATION,
+};
+use microvoxel_raycaster::composite::composite;
+use microvoxel_raycaster::distance_field::{DistanceFieldGrid, Metric};
+use microvoxel_raycaster::gbuffer::{far_depth, GBuffer, Sample};
+use microvoxel_raycaster::grid::VoxelGrid;
+use microvoxel_raycaster::heightfield::HeightField;
+use microvoxel_raycaster::interval::Interval;
+use microvoxel_raycaster::light::Light;
+use microvoxel_raycaster::mip_pyramid::OccupancyPyramid;
+use microvoxel_raycaster::octree::SparseVoxelOctree;
+use microvoxel_raycaster::palette::Palette;
+use microvoxel_raycaster::pick::pick;
+use microvoxel_raycaster::ray::Ray;
+use microvoxel_raycaster::render::render_pixels;
+use microvoxel_raycaster::traversal::{VoxelHit, VoxelTraversal};
+use microvoxel_raycaster::vox;
+use microvoxel_raycaster::voxel_stream;
+use microvoxel_raycaster::world_file;
+
+use cli::{Options, ProjectionMode, Traversal, USAGE};
+
+/// Heightmap of a walled area with a few buildings, heights in cells.
+#[rustfmt::skip]
+const WORLD: [[u8; 24]; 24] =
 [
   [1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1],
   [1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1],
@@ -30,184 +59,195 @@ use rand_pcg::Pcg64Mcg;
   [1,4,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1],
   [1,4,4,4,4,4,4,4,4,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1],
   [1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1]
-];*/
-
-const WORLD: [[[u8; 4]; 4]; 4] = [
-    [[1, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
-    [[0, 0, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
-    [[0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 1, 0], [0, 0, 0, 0]],
-    [[0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 1]],
 ];
 
-struct Interval {
-    pub min: f64,
-    pub max: f64,
+/// The 4x4x4 diagonal test pattern, cell (0, 0, 0) sits at world position (-3, -3, -3).
+fn diagonal_world() -> VoxelGrid {
+    let mut grid = VoxelGrid::new(4, 4, 4, Vector3::new(-3.0, -3.0, -3.0), 1.0);
+    // red, green, blue and yellow in the default palette
+    for (i, material) in [6, 31, 181, 36].into_iter().enumerate() {
+        grid.set(i, i, i, material);
+    }
+    grid
 }
 
-impl Interval {
-    pub const fn new(min: f64, max: f64) -> Self {
-        Self { min, max }
-    }
-    pub fn length(&self) -> f64 {
-        self.max - self.min
-    }
-    pub fn contains(&self, value: f64) -> bool {
-        self.min <= value && value <= self.max
-    }
-    pub fn surrounds(&self, value: f64) -> bool {
-        self.min < value && value < self.max
-    }
-    pub fn clamp(&self, value: f64) -> f64 {
-        value.clamp(self.min, self.max)
+/// `WORLD` as a height field, `WORLD[x][z]` is the height of column (x, z). The columns are
+/// coloured by height and the field is centered around the origin on the y = -3 plane.
+fn heightmap_world() -> HeightField {
+    // red, green, blue, yellow and white in the default palette
+    const MATERIALS: [u8; 6] = [0, 6, 31, 181, 36, 216];
+    let mut field = HeightField::new(24, 8, 24, Vector3::new(-12.0, -3.0, -12.0), 1.0);
+    for (x, row) in WORLD.iter().enumerate() {
+        for (z, &height) in row.iter().enumerate() {
+            field.set(x, z, height as usize, MATERIALS[height as usize]);
+        }
     }
-    const EMPTY: Interval = Interval::new(f64::MAX, f64::MIN);
-    const UNIVERSE: Interval = Interval::new(f64::MIN, f64::MAX);
+    field
 }
 
-struct Ray {
-    pub origin: Vector3<f64>,
-    pub dir: Vector3<f64>,
+/// Loads a scene file with its palette, the format is picked by extension.
+fn load_scene(path: &Path) -> Result<(VoxelGrid, Palette), Box<dyn Error>> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("mvxw") => Ok(world_file::load(path)?),
+        Some("mvxs") => Ok((voxel_stream::read_grid(path)?, Palette::default())),
+        Some("vox") => Ok(vox::load(path)?),
+        _ => Err("unknown scene format, expected .mvxw, .mvxs or .vox".into()),
+    }
 }
 
-impl Ray {
-    pub fn new(origin: Vector3<f64>, dir: Vector3<f64>) -> Self {
-        Self { origin, dir }
-    }
-    pub fn at(&self, t: f64) -> Vector3<f64> {
-        self.origin + self.dir * t
+fn build_traversal(grid: &VoxelGrid, traversal: Traversal) -> Box<dyn VoxelTraversal + Sync> {
+    match traversal {
+        Traversal::Dda => Box::new(grid.clone()),
+        Traversal::BrickMap => Box::new(BrickMap::from_grid(grid)),
+        Traversal::Octree => Box::new(SparseVoxelOctree::from_grid(grid)),
+        Traversal::Chebyshev => Box::new(DistanceFieldGrid::new(grid.clone(), Metric::Chebyshev)),
+        Traversal::Manhattan => Box::new(DistanceFieldGrid::new(grid.clone(), Metric::Manhattan)),
+        Traversal::Mip => Box::new(OccupancyPyramid::new(grid.clone())),
+        Traversal::HeightField => Box::new(HeightField::from_grid(grid)),
     }
 }
 
-fn main() {
-    const ASPECT_RATIO: f64 = 16.0 / 9.0;
-    const IMAGE_WIDTH: u32 = 400;
-    const CALCULATED_HEIGHT: u32 = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as u32;
-    const IMAGE_HEIGHT: u32 = if CALCULATED_HEIGHT < 1 {
-        1
-    } else {
-        CALCULATED_HEIGHT
-    };
-
-    const FOCAL_LENGTH: f64 = 1.0;
-    const VIEWPORT_HEIGHT: f64 = 2.0;
-    const VIEWPORT_WIDTH: f64 = VIEWPORT_HEIGHT * IMAGE_WIDTH as f64 / IMAGE_HEIGHT as f64;
-    const CAMERA_CENTER: Vector3<f64> = Vector3::new(0.0, 0.0, 4.0);
-
-    let viewport_u: Vector3<f64> = Vector3::new(VIEWPORT_WIDTH, 0.0, 0.0);
-    let viewport_v: Vector3<f64> = Vector3::new(0.0, -VIEWPORT_HEIGHT, 0.0);
-
-    let pixel_delta_u = viewport_u / IMAGE_WIDTH as f64;
-    let pixel_delta_v = viewport_v / IMAGE_HEIGHT as f64;
-
-    let viewport_upper_left =
-        CAMERA_CENTER - Vector3::new(0.0, 0.0, FOCAL_LENGTH) - viewport_u / 2.0 - viewport_v / 2.0;
-    let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
-
-    let mut buffer: RgbImage = ImageBuffer::new(IMAGE_WIDTH, IMAGE_HEIGHT);
-
-    for (x, y, pixel) in buffer.enumerate_pixels_mut() {
-        let pixel_sample = pixel00_loc + (x as f64 * pixel_delta_u) + (y as f64 * pixel_delta_v);
-
-        let ray = Ray::new(CAMERA_CENTER, pixel_sample - CAMERA_CENTER);
-        let mut map_x = ray.origin.x as i32;
-        let mut map_y = ray.origin.y as i32;
-        let mut map_z = ray.origin.z as i32;
-
-        let delta_dist_x = if ray.dir.x == 0.0 {
-            std::f64::MAX
-        } else {
-            (1.0 / ray.dir.x).abs()
+/// Color of the ray and the first voxel it hit, if any.
+fn ray_color(
+    ray: &Ray,
+    world: &dyn VoxelTraversal,
+    grid: &VoxelGrid,
+    palette: &Palette,
+    light: &Light,
+    shadows: bool,
+    ambient_occlusion: bool,
+) -> (Vector3<f64>, Option<VoxelHit>) {
+    let normalized_y = 0.5 * (ray.dir.normalize().y + 1.0);
+    let sky = Vector3::new(1.0, 1.0, 1.0).lerp(Vector3::new(0.5, 0.7, 1.0), normalized_y);
+    let mut first_hit = None;
+    let shade = |hit: &VoxelHit| {
+        first_hit.get_or_insert(*hit);
+        // a ray starting inside of a voxel has no entry face, light it as if facing the camera
+        let normal = match hit.face {
+            Some(face) => face.normal(),
+            None => -ray.dir.normalize(),
         };
-        let delta_dist_y = if ray.dir.y == 0.0 {
-            std::f64::MAX
-        } else {
-            (1.0 / ray.dir.y).abs()
-        };
-        let delta_dist_z = if ray.dir.z == 0.0 {
-            std::f64::MAX
-        } else {
-            (1.0 / ray.dir.z).abs()
-        };
-
-        let (step_x, mut side_dist_x) = if ray.dir.x < 0.0 {
-            (-1, (ray.origin.x - map_x as f64) * delta_dist_x)
-        } else {
-            (1, (map_x as f64 + 1.0 - ray.origin.x) * delta_dist_x)
-        };
-
-        let (step_y, mut side_dist_y) = if ray.dir.y < 0.0 {
-            (-1, (ray.origin.y - map_y as f64) * delta_dist_y)
+        const AMBIENT: f64 = 0.2;
+        let point = ray.at(hit.t);
+        let mut diffuse = normal.dot(light.direction_from(point)).max(0.0);
+        if shadows && diffuse > 0.0 && !light.visible(world, point, normal) {
+            diffuse = 0.0;
+        }
+        // how much of the light the darkest corners lose
+        const AO_STRENGTH: f64 = 0.5;
+        let occlusion = if ambient_occlusion {
+            ao::occlusion(grid, hit, point)
         } else {
-            (1, (map_y as f64 + 1.0 - ray.origin.y) * delta_dist_y)
+            1.0
         };
+        let light = (AMBIENT + (1.0 - AMBIENT) * diffuse) * (1.0 - AO_STRENGTH * (1.0 - occlusion));
+        palette.color(hit.material) * light
+    };
+    let color = composite(world, grid, palette, ray, shade, sky);
+    (color, first_hit)
+}
 
-        let (step_z, mut side_dist_z) = if ray.dir.z < 0.0 {
-            (-1, (ray.origin.z - map_z as f64) * delta_dist_z)
-        } else {
-            (1, (map_z as f64 + 1.0 - ray.origin.z) * delta_dist_z)
-        };
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            process::exit(2);
+        }
+    };
+    if options.help {
+        print!("{}", USAGE);
+        return;
+    }
 
-        let mut hit = false;
-        let mut hit_nothing = false;
-        let mut side: i32 = 0;
-        while !hit && !hit_nothing {
-            if side_dist_x < side_dist_y {
-                if side_dist_x < side_dist_z {
-                    side_dist_x += delta_dist_x;
-                    map_x += step_x;
-                    side = 0;
-                } else {
-                    side_dist_z += delta_dist_z;
-                    map_z += step_z;
-                    side = 2;
-                }
-            } else {
-                if side_dist_y < side_dist_z {
-                    side_dist_y += delta_dist_y;
-                    map_y += step_y;
-                    side = 1;
-                } else {
-                    side_dist_z += delta_dist_z;
-                    map_z += step_z;
-                    side = 2;
-                }
-            }
-            /*            if map_x > -24 && map_y > -24 && map_x <= 0 && map_y <= 0 {
-                if map_z < WORLD[-map_x as usize][-map_y as usize] as i32 && map_z >= 0 {
-                    hit = true;
-                }
-            }
-            */
-            if map_x > -4 && map_y > -4 && map_z > -4 && map_x <= 0 && map_y <= 0 && map_z <= 0 {
-                if WORLD[-map_x as usize][-map_y as usize][-map_z as usize] as i32 == 1 {
-                    hit = true;
-                }
-            }
-            /*            if map_x == 0 && map_y == 0 && map_z == -4 {
-                hit = true;
-            }*/
-            if map_x < -100
-                || map_x > 100
-                || map_y < -100
-                || map_y > 100
-                || map_z < -100
-                || map_z > 100
-            {
-                hit_nothing = true;
-            }
+    let (grid, palette) = match &options.scene {
+        Some(path) => load_scene(path).unwrap_or_else(|error| {
+            eprintln!("error: can't load {}: {}", path.display(), error);
+            process::exit(1);
+        }),
+        None if options.heightmap => (heightmap_world().to_grid(), Palette::default()),
+        None => (diagonal_world(), Palette::default()),
+    };
+    let world = build_traversal(&grid, options.traversal);
+    let distance = (options.camera - options.look_at).magnitude();
+    let (position, projection) = match options.projection {
+        ProjectionMode::Perspective => {
+            (options.camera, Projection::Perspective { fov: options.fov })
         }
-        let color: Vector3<f64> = if hit {
-            Vector3::new(1.0, 0.0, 0.0)
-        } else {
-            Vector3::new(0.0, 1.0, 0.0)
-        };
+        ProjectionMode::Orthographic => (
+            options.camera,
+            Projection::Orthographic { zoom: options.zoom },
+        ),
+        ProjectionMode::Isometric => (
+            axonometric_position(options.look_at, distance, ISOMETRIC_ELEVATION),
+            Projection::Orthographic { zoom: options.zoom },
+        ),
+        ProjectionMode::Dimetric => (
+            axonometric_position(options.look_at, distance, DIMETRIC_ELEVATION),
+            Projection::Orthographic { zoom: options.zoom },
+        ),
+    };
+    let camera = Camera::new(
+        options.width,
+        options.height,
+        position,
+        options.look_at,
+        projection,
+    );
+
+    if let Some(pixel) = options.pick {
+        match pick(&camera, world.as_ref(), pixel) {
+            Some(pick) => println!(
+                "voxel {:?} material {} face {:?} normal {:?} adjacent {:?}",
+                pick.cell,
+                pick.material,
+                pick.face,
+                pick.normal(),
+                pick.adjacent
+            ),
+            None => println!("nothing at pixel {:?}", pixel),
+        }
+        return;
+    }
+
+    let pixels = render_pixels(options.width, options.height, options.threads, |x, y| {
+        let ray = camera.get_ray(x, y);
+        let (color, first_hit) = ray_color(
+            &ray,
+            world.as_ref(),
+            &grid,
+            &palette,
+            &options.light,
+            options.shadows,
+            options.ambient_occlusion,
+        );
 
         const INTENSITY: Interval = Interval::new(0.000, 0.999);
         let ir = (256.0 * INTENSITY.clamp(color.x)) as u8;
         let ig = (256.0 * INTENSITY.clamp(color.y)) as u8;
         let ib = (256.0 * INTENSITY.clamp(color.z)) as u8;
 
-        *pixel = Rgb([ir, ig, ib]);
+        let sample = first_hit.map(|hit| Sample::new(&camera, &ray, &hit));
+        (Rgb([ir, ig, ib]), sample)
+    });
+    let buffer = RgbImage::from_fn(options.width, options.height, |x, y| {
+        pixels[(x + y * options.width) as usize].0
+    });
+    if let Err(error) = buffer.save_with_format(&options.output, options.format) {
+        eprintln!("error: can't write {}: {}", options.output.display(), error);
+        process::exit(1);
+    }
+    if let Some(prefix) = &options.gbuffer {
+        let samples = pixels.into_iter().map(|(_, sample)| sample).collect();
+        let gbuffer = GBuffer::new(options.width, options.height, samples);
+        let far = far_depth(&camera, &grid);
+        if let Err(error) = gbuffer.save(prefix, options.gbuffer_format, far) {
+            eprintln!(
+                "error: can't write the G-buffer {}: {}",
+                prefix.display(),
+                error
+            );
+            process::exit(1);
+        }
     }
-    buffer.save("render.png").unwrap();
 }
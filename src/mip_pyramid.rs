@@ -0,0 +1,188 @@
+use crate::grid::VoxelGrid;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::traversal::{grid_entry, skip_box, VoxelHit, VoxelTraversal};
+
+/// One level of occupancy bits, a bit is set when any voxel below it is solid.
+struct Level {
+    bits: Vec<u64>,
+    size: [usize; 3],
+}
+
+impl Level {
+    fn new(size: [usize; 3]) -> Self {
+        Self {
+            bits: vec![0; size.iter().product::<usize>().div_ceil(64)],
+            size,
+        }
+    }
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        x + (z * self.size[0]) + (y * self.size[0] * self.size[2])
+    }
+    fn get(&self, x: usize, y: usize, z: usize) -> bool {
+        let index = self.index(x, y, z);
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
+    fn set(&mut self, x: usize, y: usize, z: usize, occupied: bool) {
+        let index = self.index(x, y, z);
+        if occupied {
+            self.bits[index / 64] |= 1 << (index % 64);
+        } else {
+            self.bits[index / 64] &= !(1 << (index % 64));
+        }
+    }
+}
+
+/// Voxel grid with a mip pyramid of occupancy bits, every level ORs 2x2x2 cells of the level
+/// below. Traced by climbing the pyramid in empty space and descending near geometry.
+pub struct OccupancyPyramid {
+    grid: VoxelGrid,
+    levels: Vec<Level>,
+}
+
+impl OccupancyPyramid {
+    pub fn new(grid: VoxelGrid) -> Self {
+        let mut size = grid.size();
+        let mut level = Level::new(size);
+        for y in 0..size[1] {
+            for z in 0..size[2] {
+                for x in 0..size[0] {
+                    level.set(x, y, z, grid.get(x, y, z) != 0);
+                }
+            }
+        }
+        let mut levels = vec![level];
+        while size.iter().any(|s| *s > 1) {
+            size = size.map(|s| s.div_ceil(2));
+            let below = levels.last().unwrap();
+            let mut level = Level::new(size);
+            for y in 0..below.size[1] {
+                for z in 0..below.size[2] {
+                    for x in 0..below.size[0] {
+                        if below.get(x, y, z) {
+                            level.set(x / 2, y / 2, z / 2, true);
+                        }
+                    }
+                }
+            }
+            levels.push(level);
+        }
+        Self { grid, levels }
+    }
+    pub fn grid(&self) -> &VoxelGrid {
+        &self.grid
+    }
+    pub fn into_grid(self) -> VoxelGrid {
+        self.grid
+    }
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+    pub fn memory_footprint(&self) -> usize {
+        self.levels.iter().map(|level| level.bits.len() * 8).sum()
+    }
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: u8) {
+        self.grid.set(x, y, z, value);
+        let mut cell = [x, y, z];
+        let mut occupied = value != 0;
+        self.levels[0].set(x, y, z, occupied);
+        for level in 1..self.levels.len() {
+            let [x, y, z] = cell.map(|c| c / 2);
+            if !occupied {
+                // stays occupied when any of the 2x2x2 cells below still is
+                let below = &self.levels[level - 1];
+                let min = [x * 2, y * 2, z * 2];
+                occupied = (0..8).any(|i| {
+                    let [cx, cy, cz] = [min[0] + (i & 1), min[1] + (i >> 1 & 1), min[2] + (i >> 2)];
+                    cx < below.size[0]
+                        && cy < below.size[1]
+                        && cz < below.size[2]
+                        && below.get(cx, cy, cz)
+                });
+            }
+            if self.levels[level].get(x, y, z) == occupied {
+                break;
+            }
+            self.levels[level].set(x, y, z, occupied);
+            cell = [x, y, z];
+        }
+    }
+    fn occupied(&self, level: usize, cell: [i32; 3]) -> bool {
+        let [x, y, z] = cell.map(|c| (c >> level) as usize);
+        self.levels[level].get(x, y, z)
+    }
+}
+
+impl VoxelTraversal for OccupancyPyramid {
+    fn hit_counted(&self, ray: &Ray, ray_t: Interval, visited: &mut usize) -> Option<VoxelHit> {
+        let ray = Ray::new(
+            self.grid.world_to_grid(ray.origin),
+            ray.dir / self.grid.voxel_size(),
+        );
+        let size = self.grid.size();
+        let (ray_t, mut cell, mut face) = grid_entry(&ray, ray_t, size)?;
+        let mut t = ray_t.min;
+        let mut level = 0;
+        while t <= ray_t.max && (0..3).all(|axis| cell[axis] >= 0 && cell[axis] < size[axis] as i32)
+        {
+            *visited += 1;
+            while level + 1 < self.levels.len() && !self.occupied(level + 1, cell) {
+                level += 1;
+            }
+            while level > 0 && self.occupied(level, cell) {
+                level -= 1;
+            }
+            if self.occupied(level, cell) {
+                let [x, y, z] = cell.map(|c| c as usize);
+                return Some(VoxelHit {
+                    cell,
+                    t,
+                    face,
+                    material: self.grid.get(x, y, z),
+                });
+            }
+            let node_size = 1 << level;
+            let next_face;
+            (t, cell, next_face) = skip_box(&ray, cell.map(|c| c & !(node_size - 1)), node_size);
+            face = Some(next_face);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+    use rand::Rng;
+    use rand_pcg::Pcg64Mcg;
+
+    use super::*;
+
+    #[test]
+    fn set_matches_rebuild() {
+        let mut rng = Pcg64Mcg::new(5);
+        // odd sizes so the last cell of a level has no partner on some axes
+        let mut pyramid =
+            OccupancyPyramid::new(VoxelGrid::new(37, 9, 21, Vector3::new(0.0, 0.0, 0.0), 1.0));
+        let mut solid = Vec::new();
+        for _ in 0..2000 {
+            if solid.len() < 10 || rng.gen_bool(0.5) {
+                let cell = [
+                    rng.gen_range(0..37),
+                    rng.gen_range(0..9),
+                    rng.gen_range(0..21),
+                ];
+                pyramid.set(cell[0], cell[1], cell[2], rng.gen_range(1..=255));
+                solid.push(cell);
+            } else {
+                let [x, y, z] = solid.swap_remove(rng.gen_range(0..solid.len()));
+                pyramid.set(x, y, z, 0);
+            }
+            let rebuilt = OccupancyPyramid::new(pyramid.grid().clone());
+            assert_eq!(pyramid.level_count(), rebuilt.level_count());
+            for (level, expected) in pyramid.levels.iter().zip(&rebuilt.levels) {
+                assert_eq!(level.bits, expected.bits);
+            }
+        }
+    }
+}
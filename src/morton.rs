@@ -0,0 +1,33 @@
+/// Morton (Z-order) codes, interleaves the bits of x, y and z as ...zyxzyx so that voxels
+/// which are close in space are close in memory. 21 bits per axis fit in a u64.
+pub fn encode(x: u32, y: u32, z: u32) -> u64 {
+    split_by_3(x) | split_by_3(y) << 1 | split_by_3(z) << 2
+}
+
+pub fn decode(code: u64) -> [u32; 3] {
+    [
+        compact_by_3(code),
+        compact_by_3(code >> 1),
+        compact_by_3(code >> 2),
+    ]
+}
+
+fn split_by_3(value: u32) -> u64 {
+    let mut x = value as u64 & 0x1f_ffff;
+    x = (x | x << 32) & 0x1f_0000_0000_ffff;
+    x = (x | x << 16) & 0x1f_0000_ff00_00ff;
+    x = (x | x << 8) & 0x100f_00f0_0f00_f00f;
+    x = (x | x << 4) & 0x10c3_0c30_c30c_30c3;
+    x = (x | x << 2) & 0x1249_2492_4924_9249;
+    x
+}
+
+fn compact_by_3(code: u64) -> u32 {
+    let mut x = code & 0x1249_2492_4924_9249;
+    x = (x ^ (x >> 2)) & 0x10c3_0c30_c30c_30c3;
+    x = (x ^ (x >> 4)) & 0x100f_00f0_0f00_f00f;
+    x = (x ^ (x >> 8)) & 0x1f_0000_ff00_00ff;
+    x = (x ^ (x >> 16)) & 0x1f_0000_0000_ffff;
+    x = (x ^ (x >> 32)) & 0x1f_ffff;
+    x as u32
+}
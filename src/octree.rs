@@ -0,0 +1,225 @@
+use cgmath::Vector3;
+
+use crate::grid::VoxelGrid;
+use crate::interval::Interval;
+use crate::morton;
+use crate::ray::Ray;
+use crate::traversal::{grid_entry, skip_box, VoxelHit, VoxelTraversal};
+
+/// Node of a sparse voxel octree. Only children which contain voxels are stored, contiguously
+/// and in Morton order, child `i` of the mask lives at `first_child` + the number of lower
+/// bits set in `child_mask`. Nodes at voxel level are leaves and only carry a material.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct OctreeNode {
+    pub child_mask: u8,
+    pub material: u8,
+    pub first_child: u32,
+}
+
+impl OctreeNode {
+    pub fn child(&self, index: u8) -> Option<u32> {
+        if self.child_mask & (1 << index) == 0 {
+            None
+        } else {
+            let lower = self.child_mask & ((1 << index) - 1);
+            Some(self.first_child + lower.count_ones())
+        }
+    }
+}
+
+/// Node lookup result for a cell.
+enum Lookup {
+    Voxel(u8),
+    /// The cell is inside of an empty cube of `size` cells starting at `min`.
+    Empty {
+        min: [i32; 3],
+        size: i32,
+    },
+}
+
+/// Sparse voxel octree over a cube of 2^depth cells, empty space costs nothing.
+/// Nodes are stored level by level from the root and Morton ordered within a level,
+/// so siblings and nearby nodes are close in memory.
+pub struct SparseVoxelOctree {
+    nodes: Vec<OctreeNode>,
+    root: u32,
+    depth: u32,
+    size: [usize; 3],
+    origin: Vector3<f64>,
+    voxel_size: f64,
+}
+
+impl SparseVoxelOctree {
+    /// Nodes as produced by a builder, `root` indexes into `nodes`.
+    pub fn from_nodes(
+        nodes: Vec<OctreeNode>,
+        root: u32,
+        depth: u32,
+        size: [usize; 3],
+        origin: Vector3<f64>,
+        voxel_size: f64,
+    ) -> Self {
+        Self {
+            nodes,
+            root,
+            depth,
+            size,
+            origin,
+            voxel_size,
+        }
+    }
+    pub fn from_grid(grid: &VoxelGrid) -> Self {
+        let size = grid.size();
+        let depth = depth_for(size);
+
+        let mut voxels = Vec::new();
+        for y in 0..size[1] {
+            for z in 0..size[2] {
+                for x in 0..size[0] {
+                    let material = grid.get(x, y, z);
+                    if material != 0 {
+                        voxels.push((morton::encode(x as u32, y as u32, z as u32), material));
+                    }
+                }
+            }
+        }
+        voxels.sort_unstable_by_key(|(code, _)| *code);
+
+        // build bottom up, every level holds Morton codes and nodes with level local child indices
+        let mut levels: Vec<Vec<(u64, OctreeNode)>> = vec![voxels
+            .into_iter()
+            .map(|(code, material)| {
+                (
+                    code,
+                    OctreeNode {
+                        material,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect()];
+        for _ in 0..depth {
+            let children = levels.last().unwrap();
+            let mut parents: Vec<(u64, OctreeNode)> = Vec::new();
+            for (index, (code, _)) in children.iter().enumerate() {
+                let parent_code = code >> 3;
+                if parents.last().map(|(code, _)| *code) != Some(parent_code) {
+                    parents.push((
+                        parent_code,
+                        OctreeNode {
+                            first_child: index as u32,
+                            ..Default::default()
+                        },
+                    ));
+                }
+                parents.last_mut().unwrap().1.child_mask |= 1 << (code & 7);
+            }
+            levels.push(parents);
+        }
+        levels.reverse();
+        if levels[0].is_empty() {
+            levels[0].push((0, OctreeNode::default()));
+        }
+
+        let mut nodes = Vec::with_capacity(levels.iter().map(|level| level.len()).sum());
+        for (depth, level) in levels.iter().enumerate() {
+            let children_offset = (nodes.len() + level.len()) as u32;
+            nodes.extend(level.iter().map(|(_, node)| {
+                let mut node = *node;
+                if depth < levels.len() - 1 {
+                    node.first_child += children_offset;
+                }
+                node
+            }));
+        }
+        Self::from_nodes(nodes, 0, depth, size, grid.origin(), grid.voxel_size())
+    }
+    pub fn root(&self) -> OctreeNode {
+        self.nodes[self.root as usize]
+    }
+    pub fn node(&self, index: u32) -> OctreeNode {
+        self.nodes[index as usize]
+    }
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+    pub fn memory_footprint(&self) -> usize {
+        self.nodes.len() * std::mem::size_of::<OctreeNode>()
+    }
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        match self.lookup([x as i32, y as i32, z as i32]) {
+            Lookup::Voxel(material) => material,
+            Lookup::Empty { .. } => 0,
+        }
+    }
+    fn lookup(&self, cell: [i32; 3]) -> Lookup {
+        let mut node = self.nodes[self.root as usize];
+        for level in (0..self.depth).rev() {
+            let child = ((cell[0] >> level) & 1)
+                | ((cell[1] >> level) & 1) << 1
+                | ((cell[2] >> level) & 1) << 2;
+            match node.child(child as u8) {
+                Some(index) => node = self.nodes[index as usize],
+                None => {
+                    let size = 1 << level;
+                    return Lookup::Empty {
+                        min: cell.map(|c| c & !(size - 1)),
+                        size,
+                    };
+                }
+            }
+        }
+        match node.material {
+            0 => Lookup::Empty {
+                min: [0; 3],
+                size: 1 << self.depth,
+            },
+            material => Lookup::Voxel(material),
+        }
+    }
+}
+
+/// Depth of the smallest power of two cube containing `size`.
+pub fn depth_for(size: [usize; 3]) -> u32 {
+    let side = size
+        .into_iter()
+        .max()
+        .unwrap_or(1)
+        .max(1)
+        .next_power_of_two();
+    side.trailing_zeros()
+}
+
+impl VoxelTraversal for SparseVoxelOctree {
+    /// Looks up the node containing the current cell and jumps over it in one go when it is
+    /// empty, the bigger the empty node the bigger the jump.
+    fn hit_counted(&self, ray: &Ray, ray_t: Interval, visited: &mut usize) -> Option<VoxelHit> {
+        let ray = Ray::new(
+            (ray.origin - self.origin) / self.voxel_size,
+            ray.dir / self.voxel_size,
+        );
+        let (ray_t, mut cell, mut face) = grid_entry(&ray, ray_t, self.size)?;
+        let mut t = ray_t.min;
+        while t <= ray_t.max
+            && (0..3).all(|axis| cell[axis] >= 0 && cell[axis] < self.size[axis] as i32)
+        {
+            *visited += 1;
+            match self.lookup(cell) {
+                Lookup::Voxel(material) => {
+                    return Some(VoxelHit {
+                        cell,
+                        t,
+                        face,
+                        material,
+                    })
+                }
+                Lookup::Empty { min, size } => {
+                    let next_face;
+                    (t, cell, next_face) = skip_box(&ray, min, size);
+                    face = Some(next_face);
+                }
+            }
+        }
+        None
+    }
+}
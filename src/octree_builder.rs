@@ -0,0 +1,406 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use cgmath::Vector3;
+
+use crate::morton;
+use crate::octree::{depth_for, OctreeNode, SparseVoxelOctree};
+use crate::voxel_stream::VoxelStreamReader;
+
+const MAGIC: [u8; 4] = *b"MVXO";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 32;
+const NODE_SIZE: usize = 6;
+/// Morton codes hold 21 bits per axis.
+const MAX_DEPTH: u32 = 21;
+
+/// Out-of-core sparse voxel octree construction, after
+/// https://www.forceflow.be/2012/07/24/out-of-core-construction-of-sparse-voxel-octrees/
+///
+/// Voxels arrive in Morton order, so all children of a node arrive one after the other. Every
+/// level keeps a queue with the children of the node it is currently filling, when a voxel for
+/// another node arrives the children are written to the node file and their parent is queued
+/// one level up. Memory use is 8 nodes per level, independent of the world size.
+///
+/// Node file layout, all numbers little endian:
+///
+/// | bytes | content                                             |
+/// |-------|-----------------------------------------------------|
+/// | 4     | magic "MVXO"                                        |
+/// | 4     | version                                             |
+/// | 12    | size x, y, z as u32                                 |
+/// | 4     | depth                                               |
+/// | 4     | root node index                                     |
+/// | 4     | node count                                          |
+/// | 6 * n | child mask u8, material u8, first child index u32   |
+///
+/// Children are written before their parents so the root is the last node.
+struct Builder<W: Write> {
+    writer: W,
+    depth: u32,
+    queues: Vec<Queue>,
+    node_count: u32,
+    root: Option<OctreeNode>,
+}
+
+#[derive(Default)]
+struct Queue {
+    parent: u64,
+    children: Vec<(u8, OctreeNode)>,
+}
+
+impl<W: Write> Builder<W> {
+    fn new(writer: W, depth: u32) -> Self {
+        Self {
+            writer,
+            depth,
+            queues: (0..=depth).map(|_| Queue::default()).collect(),
+            node_count: 0,
+            root: None,
+        }
+    }
+    /// Queues a node with Morton `code` at `level`, level `depth` holds the voxels.
+    fn push(&mut self, level: u32, code: u64, node: OctreeNode) -> io::Result<()> {
+        if level == 0 {
+            self.root = Some(node);
+            return Ok(());
+        }
+        let parent = code >> 3;
+        let queue = &self.queues[level as usize];
+        if !queue.children.is_empty() && queue.parent != parent {
+            self.flush(level)?;
+        }
+        let queue = &mut self.queues[level as usize];
+        queue.parent = parent;
+        queue.children.push(((code & 7) as u8, node));
+        Ok(())
+    }
+    /// Writes the queued children at `level` and queues their parent.
+    fn flush(&mut self, level: u32) -> io::Result<()> {
+        let queue = std::mem::take(&mut self.queues[level as usize]);
+        if queue.children.is_empty() {
+            return Ok(());
+        }
+        let mut parent = OctreeNode {
+            first_child: self.node_count,
+            ..Default::default()
+        };
+        for (index, node) in queue.children {
+            parent.child_mask |= 1 << index;
+            self.write_node(&node)?;
+        }
+        self.push(level - 1, queue.parent, parent)
+    }
+    fn write_node(&mut self, node: &OctreeNode) -> io::Result<()> {
+        let mut record = [0; NODE_SIZE];
+        record[0] = node.child_mask;
+        record[1] = node.material;
+        record[2..6].copy_from_slice(&node.first_child.to_le_bytes());
+        self.writer.write_all(&record)?;
+        self.node_count += 1;
+        Ok(())
+    }
+    /// Flushes all levels bottom up and writes the root, returns its index.
+    fn finish(mut self) -> io::Result<(W, u32, u32)> {
+        for level in (1..=self.depth).rev() {
+            self.flush(level)?;
+        }
+        let root = self.root.unwrap_or_default();
+        let root_index = self.node_count;
+        self.write_node(&root)?;
+        Ok((self.writer, root_index, self.node_count))
+    }
+}
+
+/// Builds a node file from a Morton sorted voxel stream without holding the world in memory.
+pub fn build(voxel_stream: &Path, node_file: &Path) -> io::Result<()> {
+    let voxels = VoxelStreamReader::open(voxel_stream)?;
+    let size = voxels.size();
+    let depth = depth_for(size);
+
+    let mut file = File::create(node_file)?;
+    // header is written again at the end when the root and node count are known
+    write_header(&mut file, size, depth, 0, 0)?;
+    let mut builder = Builder::new(BufWriter::new(file), depth);
+    let mut last_code = None;
+    for voxel in voxels {
+        let (code, material) = voxel?;
+        if last_code.is_some_and(|last| last >= code) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "voxel stream is not sorted in Morton order",
+            ));
+        }
+        last_code = Some(code);
+        let [x, y, z] = morton::decode(code);
+        if x as usize >= size[0] || y as usize >= size[1] || z as usize >= size[2] {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "voxel outside of the voxel stream size",
+            ));
+        }
+        if material != 0 {
+            let leaf = OctreeNode {
+                material,
+                ..Default::default()
+            };
+            builder.push(depth, code, leaf)?;
+        }
+    }
+    let (writer, root, node_count) = builder.finish()?;
+    let mut file = writer.into_inner().map_err(|error| error.into_error())?;
+    file.seek(SeekFrom::Start(0))?;
+    write_header(&mut file, size, depth, root, node_count)
+}
+
+fn write_header(
+    writer: &mut impl Write,
+    size: [usize; 3],
+    depth: u32,
+    root: u32,
+    node_count: u32,
+) -> io::Result<()> {
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    for s in size {
+        header.extend_from_slice(&(s as u32).to_le_bytes());
+    }
+    header.extend_from_slice(&depth.to_le_bytes());
+    header.extend_from_slice(&root.to_le_bytes());
+    header.extend_from_slice(&node_count.to_le_bytes());
+    writer.write_all(&header)
+}
+
+/// Loads a node file produced by `build` for tracing, unit voxels at the origin.
+pub fn load(node_file: &Path) -> io::Result<SparseVoxelOctree> {
+    let mut reader = BufReader::new(File::open(node_file)?);
+    let mut header = [0; HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+    let read_u32 =
+        |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+    if header[0..4] != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not an octree node file",
+        ));
+    }
+    if read_u32(4) != VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported octree node file version {}", read_u32(4)),
+        ));
+    }
+    let size = [
+        read_u32(8) as usize,
+        read_u32(12) as usize,
+        read_u32(16) as usize,
+    ];
+    let depth = read_u32(20);
+    let root = read_u32(24);
+    let node_count = read_u32(28);
+    if depth > MAX_DEPTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("octree depth {} is too large", depth),
+        ));
+    }
+    if root >= node_count {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "octree root outside of the node file",
+        ));
+    }
+
+    let mut nodes = Vec::with_capacity(node_count as usize);
+    let mut record = [0; NODE_SIZE];
+    for _ in 0..node_count {
+        reader.read_exact(&mut record)?;
+        let node = OctreeNode {
+            child_mask: record[0],
+            material: record[1],
+            first_child: u32::from_le_bytes(record[2..6].try_into().unwrap()),
+        };
+        let children_end = node.first_child as u64 + node.child_mask.count_ones() as u64;
+        if node.child_mask != 0 && children_end > node_count as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "octree node children outside of the node file",
+            ));
+        }
+        nodes.push(node);
+    }
+    Ok(SparseVoxelOctree::from_nodes(
+        nodes,
+        root,
+        depth,
+        size,
+        Vector3::new(0.0, 0.0, 0.0),
+        1.0,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rand::Rng;
+    use rand_pcg::Pcg64Mcg;
+
+    use super::*;
+    use crate::grid::VoxelGrid;
+    use crate::morton;
+    use crate::voxel_stream::{self, VoxelStreamWriter};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("microvoxel-{}-{}", std::process::id(), name))
+    }
+
+    /// Walks both trees from the root and compares every node, the builder stores the nodes
+    /// in a different order so the indices differ.
+    fn assert_same_tree(
+        a: &SparseVoxelOctree,
+        a_node: OctreeNode,
+        b: &SparseVoxelOctree,
+        b_node: OctreeNode,
+    ) {
+        assert_eq!(a_node.child_mask, b_node.child_mask);
+        assert_eq!(a_node.material, b_node.material);
+        for child in 0..8 {
+            if let (Some(a_child), Some(b_child)) = (a_node.child(child), b_node.child(child)) {
+                assert_same_tree(a, a.node(a_child), b, b.node(b_child));
+            }
+        }
+    }
+
+    #[test]
+    fn built_octree_matches_from_grid() {
+        let mut rng = Pcg64Mcg::new(7);
+        let mut grid = VoxelGrid::new(21, 13, 9, Vector3::new(0.0, 0.0, 0.0), 1.0);
+        for _ in 0..400 {
+            let (x, y, z) = (
+                rng.gen_range(0..21),
+                rng.gen_range(0..13),
+                rng.gen_range(0..9),
+            );
+            grid.set(x, y, z, rng.gen_range(1..=255));
+        }
+        let stream = temp_path("built.mvxs");
+        let nodes = temp_path("built.mvxo");
+        voxel_stream::write_grid(&stream, &grid).unwrap();
+        build(&stream, &nodes).unwrap();
+        let loaded = load(&nodes).unwrap();
+        std::fs::remove_file(&stream).unwrap();
+        std::fs::remove_file(&nodes).unwrap();
+
+        let expected = SparseVoxelOctree::from_grid(&grid);
+        assert_eq!(loaded.node_count(), expected.node_count());
+        assert_same_tree(&loaded, loaded.root(), &expected, expected.root());
+        for y in 0..13 {
+            for z in 0..9 {
+                for x in 0..21 {
+                    assert_eq!(loaded.get(x, y, z), grid.get(x, y, z));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn build_rejects_unsorted_stream() {
+        let mut data = VoxelStreamWriter::new(Vec::new(), [4, 4, 4])
+            .unwrap()
+            .finish()
+            .unwrap();
+        for code in [morton::encode(1, 1, 1), morton::encode(0, 0, 1)] {
+            data.extend_from_slice(&code.to_le_bytes());
+            data.push(1);
+        }
+        let stream = temp_path("unsorted.mvxs");
+        let nodes = temp_path("unsorted.mvxo");
+        std::fs::write(&stream, data).unwrap();
+        let error = build(&stream, &nodes).unwrap_err();
+        std::fs::remove_file(&stream).unwrap();
+        let _ = std::fs::remove_file(&nodes);
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn build_rejects_voxel_outside_of_size() {
+        let mut data = VoxelStreamWriter::new(Vec::new(), [4, 4, 4])
+            .unwrap()
+            .finish()
+            .unwrap();
+        for code in [morton::encode(1, 1, 1), morton::encode(5, 0, 0)] {
+            data.extend_from_slice(&code.to_le_bytes());
+            data.push(1);
+        }
+        let stream = temp_path("outside.mvxs");
+        let nodes = temp_path("outside.mvxo");
+        std::fs::write(&stream, data).unwrap();
+        let error = build(&stream, &nodes).unwrap_err();
+        std::fs::remove_file(&stream).unwrap();
+        let _ = std::fs::remove_file(&nodes);
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn writer_rejects_voxel_outside_of_size() {
+        let mut writer = VoxelStreamWriter::new(Vec::new(), [4, 4, 4]).unwrap();
+        writer.write(morton::encode(1, 1, 1), 1).unwrap();
+        let error = writer.write(morton::encode(5, 0, 0), 1).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    /// Builds a small node file and returns its bytes.
+    fn node_file_bytes(name: &str) -> Vec<u8> {
+        let mut grid = VoxelGrid::new(6, 5, 7, Vector3::new(0.0, 0.0, 0.0), 1.0);
+        grid.set(1, 2, 3, 4);
+        grid.set(5, 4, 6, 9);
+        let stream = temp_path(&format!("{}.mvxs", name));
+        let nodes = temp_path(&format!("{}.mvxo", name));
+        voxel_stream::write_grid(&stream, &grid).unwrap();
+        build(&stream, &nodes).unwrap();
+        let bytes = std::fs::read(&nodes).unwrap();
+        std::fs::remove_file(&stream).unwrap();
+        std::fs::remove_file(&nodes).unwrap();
+        bytes
+    }
+
+    /// Loads the bytes as a node file and returns the error, if any.
+    fn load_error(name: &str, bytes: &[u8]) -> Option<io::Error> {
+        let path = temp_path(name);
+        std::fs::write(&path, bytes).unwrap();
+        let result = load(&path);
+        std::fs::remove_file(&path).unwrap();
+        result.err()
+    }
+
+    #[test]
+    fn load_rejects_truncated_node_file() {
+        let bytes = node_file_bytes("truncated");
+        assert!(load_error("truncated-full.mvxo", &bytes).is_none());
+        let error = load_error("truncated.mvxo", &bytes[..bytes.len() - NODE_SIZE]).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        let error = load_error("truncated-header.mvxo", &bytes[..HEADER_SIZE - 1]).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn load_rejects_corrupt_node_file() {
+        let bytes = node_file_bytes("corrupt");
+
+        // children past the last node
+        let root = u32::from_le_bytes(bytes[24..28].try_into().unwrap()) as usize;
+        let mut corrupt = bytes.clone();
+        let first_child = HEADER_SIZE + root * NODE_SIZE + 2;
+        corrupt[first_child..first_child + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let error = load_error("corrupt-child.mvxo", &corrupt).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let mut corrupt = bytes;
+        corrupt[20..24].copy_from_slice(&(MAX_DEPTH + 1).to_le_bytes());
+        let error = load_error("corrupt-depth.mvxo", &corrupt).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,52 @@
+use cgmath::Vector3;
+
+/// 256 colour palette indexed by voxel material, entry 0 is empty space.
+/// Colours are packed like the lattice: 0xAABBGGRR.
+pub struct Palette {
+    pub colors: [u32; 256],
+}
+
+impl Palette {
+    pub fn new() -> Self {
+        Self { colors: [0; 256] }
+    }
+    pub fn set(&mut self, index: u8, rgba: u32) {
+        self.colors[index as usize] = rgba;
+    }
+    pub fn get(&self, index: u8) -> u32 {
+        self.colors[index as usize]
+    }
+    pub fn color(&self, index: u8) -> Vector3<f64> {
+        let rgba = self.get(index);
+        Vector3::new(
+            (rgba & 0xFF) as f64 / 255.0,
+            ((rgba >> 8) & 0xFF) as f64 / 255.0,
+            ((rgba >> 16) & 0xFF) as f64 / 255.0,
+        )
+    }
+    pub fn alpha(&self, index: u8) -> f64 {
+        (self.get(index) >> 24) as f64 / 255.0
+    }
+}
+
+pub fn pack_rgba(r: u8, g: u8, b: u8, a: u8) -> u32 {
+    r as u32 | (g as u32) << 8 | (b as u32) << 16 | (a as u32) << 24
+}
+
+impl Default for Palette {
+    /// 6x6x6 colour cube in entries 1..=216 followed by a grey ramp.
+    fn default() -> Self {
+        let mut palette = Palette::new();
+        for i in 0..216u32 {
+            let r = (i % 6) * 51;
+            let g = (i / 6 % 6) * 51;
+            let b = (i / 36) * 51;
+            palette.set(1 + i as u8, pack_rgba(r as u8, g as u8, b as u8, 0xFF));
+        }
+        for i in 217..=255u32 {
+            let grey = ((i - 217) * 255 / 38) as u8;
+            palette.set(i as u8, pack_rgba(grey, grey, grey, 0xFF));
+        }
+        palette
+    }
+}
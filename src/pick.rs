@@ -0,0 +1,47 @@
+use crate::camera::Camera;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::traversal::{Face, VoxelTraversal};
+
+/// Voxel under a pixel, for placing and removing voxels in an editor.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Pick {
+    pub cell: [i32; 3],
+    pub material: u8,
+    /// Face the ray entered the voxel through, None when the ray starts inside of it.
+    pub face: Option<Face>,
+    /// Empty cell in front of the face, where a voxel placed onto the face goes. It can be
+    /// outside of the grid when the face is on the boundary.
+    pub adjacent: Option<[i32; 3]>,
+}
+
+impl Pick {
+    /// Outward normal of the picked face in cells.
+    pub fn normal(&self) -> Option<[i32; 3]> {
+        self.face.map(|face| face.offset())
+    }
+}
+
+/// First voxel along a world space ray.
+pub fn pick_ray(world: &dyn VoxelTraversal, ray: &Ray) -> Option<Pick> {
+    let hit = world.hit(ray, Interval::new(0.0, f64::INFINITY))?;
+    Some(Pick {
+        cell: hit.cell,
+        material: hit.material,
+        face: hit.face,
+        adjacent: hit.face.map(|face| {
+            let offset = face.offset();
+            std::array::from_fn(|axis| hit.cell[axis] + offset[axis])
+        }),
+    })
+}
+
+/// Voxel under `pixel` of the camera's viewport, traced with the same ray the pixel is
+/// rendered with. None when nothing is hit or the pixel is outside of the viewport.
+pub fn pick(camera: &Camera, world: &dyn VoxelTraversal, pixel: [u32; 2]) -> Option<Pick> {
+    let [width, height] = camera.viewport();
+    if pixel[0] >= width || pixel[1] >= height {
+        return None;
+    }
+    pick_ray(world, &camera.get_ray(pixel[0], pixel[1]))
+}
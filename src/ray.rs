@@ -0,0 +1,15 @@
+use cgmath::Vector3;
+
+pub struct Ray {
+    pub origin: Vector3<f64>,
+    pub dir: Vector3<f64>,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3<f64>, dir: Vector3<f64>) -> Self {
+        Self { origin, dir }
+    }
+    pub fn at(&self, t: f64) -> Vector3<f64> {
+        self.origin + self.dir * t
+    }
+}
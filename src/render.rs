@@ -0,0 +1,93 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use image::{Rgb, RgbImage};
+
+/// Width and height in pixels of the tiles the image is split in.
+pub const TILE_SIZE: u32 = 16;
+
+/// Threads to render with when nothing else is asked for, one per core.
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+/// Renders an image in square tiles spread over `threads` threads. Tiles are handed out from a
+/// shared counter so threads that finish early pick up more work.
+///
+/// `shade` is called once for every pixel, as long as its result only depends on the pixel
+/// coordinates the image is the same for any thread count.
+pub fn render_tiles<F>(width: u32, height: u32, threads: usize, shade: F) -> RgbImage
+where
+    F: Fn(u32, u32) -> Rgb<u8> + Sync,
+{
+    let pixels = render_pixels(width, height, threads, shade);
+    RgbImage::from_fn(width, height, |x, y| pixels[(x + y * width) as usize])
+}
+
+/// Like `render_tiles` for anything computed per pixel, returns the pixels row by row.
+pub fn render_pixels<T, F>(width: u32, height: u32, threads: usize, shade: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(u32, u32) -> T + Sync,
+{
+    let tiles_x = width.div_ceil(TILE_SIZE);
+    let tile_count = (tiles_x * height.div_ceil(TILE_SIZE)) as usize;
+    let next_tile = AtomicUsize::new(0);
+    let buffer: Mutex<Vec<Option<T>>> = Mutex::new((0..width * height).map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| loop {
+                let tile = next_tile.fetch_add(1, Ordering::Relaxed);
+                if tile >= tile_count {
+                    break;
+                }
+                let x_min = (tile as u32 % tiles_x) * TILE_SIZE;
+                let y_min = (tile as u32 / tiles_x) * TILE_SIZE;
+                let x_max = (x_min + TILE_SIZE).min(width);
+                let y_max = (y_min + TILE_SIZE).min(height);
+                let pixels: Vec<(u32, u32, T)> = (y_min..y_max)
+                    .flat_map(|y| (x_min..x_max).map(move |x| (x, y)))
+                    .map(|(x, y)| (x, y, shade(x, y)))
+                    .collect();
+                let mut buffer = buffer.lock().unwrap();
+                for (x, y, pixel) in pixels {
+                    buffer[(x + y * width) as usize] = Some(pixel);
+                }
+            });
+        }
+    });
+    // every tile was rendered, so every pixel is set
+    buffer
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(Option::unwrap)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheap stand in for tracing a ray, different for every pixel.
+    fn shade(x: u32, y: u32) -> u64 {
+        let mut hash = (x as u64) << 32 | y as u64;
+        for _ in 0..100 {
+            hash = hash.wrapping_mul(0x9E37_79B9_7F4A_7C15).rotate_left(17);
+        }
+        hash
+    }
+
+    #[test]
+    fn image_is_the_same_for_any_thread_count() {
+        // not a multiple of the tile size so the edge tiles are partial
+        let (width, height) = (75, 41);
+        let single = render_pixels(width, height, 1, shade);
+        assert_eq!(single.len(), (width * height) as usize);
+        assert_eq!(single[(3 + 7 * width) as usize], shade(3, 7));
+        for threads in [2, 3, 8, 32] {
+            assert_eq!(render_pixels(width, height, threads, shade), single);
+        }
+    }
+}
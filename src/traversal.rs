@@ -0,0 +1,282 @@
+use cgmath::Vector3;
+
+use crate::aabb::Aabb;
+use crate::interval::Interval;
+use crate::ray::Ray;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Face {
+    NegX,
+    PosX,
+    NegY,
+    PosY,
+    NegZ,
+    PosZ,
+}
+
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::NegX,
+        Face::PosX,
+        Face::NegY,
+        Face::PosY,
+        Face::NegZ,
+        Face::PosZ,
+    ];
+    /// Face a cell is entered through when stepping along `axis` (0, 1 or 2) in direction `step`.
+    pub fn entered(axis: usize, step: i32) -> Self {
+        match (axis, step < 0) {
+            (0, false) => Face::NegX,
+            (0, true) => Face::PosX,
+            (1, false) => Face::NegY,
+            (1, true) => Face::PosY,
+            (2, false) => Face::NegZ,
+            (2, true) => Face::PosZ,
+            _ => panic!("axis out of range: {}", axis),
+        }
+    }
+    /// Outward normal of the face.
+    pub fn normal(&self) -> Vector3<f64> {
+        match self {
+            Face::NegX => Vector3::new(-1.0, 0.0, 0.0),
+            Face::PosX => Vector3::new(1.0, 0.0, 0.0),
+            Face::NegY => Vector3::new(0.0, -1.0, 0.0),
+            Face::PosY => Vector3::new(0.0, 1.0, 0.0),
+            Face::NegZ => Vector3::new(0.0, 0.0, -1.0),
+            Face::PosZ => Vector3::new(0.0, 0.0, 1.0),
+        }
+    }
+    /// Outward normal of the face in cells, the offset to the neighbour across the face.
+    pub fn offset(&self) -> [i32; 3] {
+        match self {
+            Face::NegX => [-1, 0, 0],
+            Face::PosX => [1, 0, 0],
+            Face::NegY => [0, -1, 0],
+            Face::PosY => [0, 1, 0],
+            Face::NegZ => [0, 0, -1],
+            Face::PosZ => [0, 0, 1],
+        }
+    }
+    pub fn axis(&self) -> usize {
+        match self {
+            Face::NegX | Face::PosX => 0,
+            Face::NegY | Face::PosY => 1,
+            Face::NegZ | Face::PosZ => 2,
+        }
+    }
+}
+
+/// A cell visited by a ray, `face` is None for the cell the ray starts in.
+#[derive(Debug, Copy, Clone)]
+pub struct VoxelStep {
+    pub cell: [i32; 3],
+    pub t_enter: f64,
+    pub t_exit: f64,
+    pub face: Option<Face>,
+}
+
+/// First solid voxel along a ray, `face` is None when the ray starts inside of it.
+#[derive(Debug, Copy, Clone)]
+pub struct VoxelHit {
+    pub cell: [i32; 3],
+    pub t: f64,
+    pub face: Option<Face>,
+    pub material: u8,
+}
+
+/// Voxel storage that can be traced, implemented by the grid and the acceleration structures
+/// so they can be swapped and compared on the same scene.
+pub trait VoxelTraversal {
+    /// First solid voxel along a world space ray within `ray_t`.
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<VoxelHit> {
+        self.hit_counted(ray, ray_t, &mut 0)
+    }
+    /// Same as `hit`, adds the number of cells, bricks or nodes looked at on the way to
+    /// `visited` so the traversals can be compared.
+    fn hit_counted(&self, ray: &Ray, ray_t: Interval, visited: &mut usize) -> Option<VoxelHit>;
+}
+
+/// Clips a grid space ray against a grid of `size` cells, returns the clipped interval,
+/// the first cell and the face that cell is entered through.
+pub(crate) fn grid_entry(
+    ray: &Ray,
+    ray_t: Interval,
+    size: [usize; 3],
+) -> Option<(Interval, [i32; 3], Option<Face>)> {
+    let bounds = Aabb::new(
+        Vector3::new(0.0, 0.0, 0.0),
+        Vector3::new(size[0] as f64, size[1] as f64, size[2] as f64),
+    );
+    let t_min = ray_t.min;
+    let ray_t = bounds.hit(ray, ray_t)?;
+    let start = ray.at(ray_t.min);
+    let mut cell = [0; 3];
+    let mut entry_axis = None;
+    let mut t_entry = t_min;
+    for axis in 0..3 {
+        // the entry point is on the boundary, clamp so rounding can't put it outside
+        cell[axis] = (start[axis].floor() as i32).clamp(0, size[axis] as i32 - 1);
+        if ray.dir[axis] != 0.0 {
+            let plane = if ray.dir[axis] < 0.0 {
+                size[axis] as f64
+            } else {
+                0.0
+            };
+            let t_plane = (plane - ray.origin[axis]) / ray.dir[axis];
+            if t_plane >= t_entry {
+                t_entry = t_plane;
+                entry_axis = Some(axis);
+            }
+        }
+    }
+    let face = entry_axis.map(|axis| Face::entered(axis, step_sign(ray.dir[axis])));
+    Some((ray_t, cell, face))
+}
+
+/// Moves a grid space ray past the cube of cells `min..min + size`, used to skip empty space.
+/// Returns the t at which the ray leaves the cube, the cell it steps into and the face of
+/// that cell it enters through.
+pub(crate) fn skip_box(ray: &Ray, min: [i32; 3], size: i32) -> (f64, [i32; 3], Face) {
+    let mut t_exit = f64::INFINITY;
+    let mut exit_axis = 0;
+    for (axis, &min_axis) in min.iter().enumerate() {
+        if ray.dir[axis] != 0.0 {
+            let plane = if ray.dir[axis] < 0.0 {
+                min_axis
+            } else {
+                min_axis + size
+            };
+            let t_plane = (plane as f64 - ray.origin[axis]) / ray.dir[axis];
+            if t_plane < t_exit {
+                t_exit = t_plane;
+                exit_axis = axis;
+            }
+        }
+    }
+    let exit = ray.at(t_exit);
+    let mut cell: [i32; 3] = std::array::from_fn(|axis| {
+        (exit[axis].floor() as i32).clamp(min[axis], min[axis] + size - 1)
+    });
+    let step = step_sign(ray.dir[exit_axis]);
+    cell[exit_axis] = if step < 0 {
+        min[exit_axis] - 1
+    } else {
+        min[exit_axis] + size
+    };
+    (t_exit, cell, Face::entered(exit_axis, step))
+}
+
+fn step_sign(dir: f64) -> i32 {
+    if dir < 0.0 {
+        -1
+    } else {
+        1
+    }
+}
+
+/// Amanatides-Woo traversal over the unit cells of grid space.
+/// Yields every cell the ray passes through within `ray_t`, front to back.
+pub struct VoxelRayIter {
+    map: [i32; 3],
+    step: [i32; 3],
+    side_dist: [f64; 3],
+    delta_dist: [f64; 3],
+    t: f64,
+    t_max: f64,
+    face: Option<Face>,
+    bounds: Option<[i32; 3]>,
+}
+
+impl VoxelRayIter {
+    pub fn new(ray: &Ray, ray_t: Interval) -> Self {
+        let start = ray.at(ray_t.min);
+        let map = [
+            start.x.floor() as i32,
+            start.y.floor() as i32,
+            start.z.floor() as i32,
+        ];
+        Self::start_at(ray, ray_t, map, None)
+    }
+    /// Traversal of a grid with `size` cells starting at the origin of grid space.
+    /// The ray is clipped against the grid bounds first so the DDA starts at the entry point
+    /// and stops at the exit point.
+    pub fn clipped(ray: &Ray, ray_t: Interval, size: [usize; 3]) -> Self {
+        let Some((ray_t, map, face)) = grid_entry(ray, ray_t, size) else {
+            return Self::start_at(ray, Interval::EMPTY, [0; 3], None);
+        };
+        let mut iter = Self::start_at(ray, ray_t, map, face);
+        iter.bounds = Some([size[0] as i32, size[1] as i32, size[2] as i32]);
+        iter
+    }
+    fn start_at(ray: &Ray, ray_t: Interval, map: [i32; 3], face: Option<Face>) -> Self {
+        let start = ray.at(ray_t.min);
+        let mut step = [0; 3];
+        let mut side_dist = [0.0; 3];
+        let mut delta_dist = [0.0; 3];
+        for axis in 0..3 {
+            delta_dist[axis] = if ray.dir[axis] == 0.0 {
+                f64::INFINITY
+            } else {
+                (1.0 / ray.dir[axis]).abs()
+            };
+            (step[axis], side_dist[axis]) = if ray.dir[axis] < 0.0 {
+                (
+                    -1,
+                    ray_t.min + (start[axis] - map[axis] as f64) * delta_dist[axis],
+                )
+            } else {
+                (
+                    1,
+                    ray_t.min + (map[axis] as f64 + 1.0 - start[axis]) * delta_dist[axis],
+                )
+            };
+        }
+        Self {
+            map,
+            step,
+            side_dist,
+            delta_dist,
+            t: ray_t.min,
+            t_max: ray_t.max,
+            face,
+            bounds: None,
+        }
+    }
+}
+
+impl Iterator for VoxelRayIter {
+    type Item = VoxelStep;
+
+    fn next(&mut self) -> Option<VoxelStep> {
+        if self.t > self.t_max || self.t.is_infinite() {
+            return None;
+        }
+        if let Some(bounds) = self.bounds {
+            if (0..3).any(|axis| self.map[axis] < 0 || self.map[axis] >= bounds[axis]) {
+                return None;
+            }
+        }
+        let side = if self.side_dist[0] < self.side_dist[1] {
+            if self.side_dist[0] < self.side_dist[2] {
+                0
+            } else {
+                2
+            }
+        } else if self.side_dist[1] < self.side_dist[2] {
+            1
+        } else {
+            2
+        };
+        let current = VoxelStep {
+            cell: self.map,
+            t_enter: self.t,
+            t_exit: self.side_dist[side].min(self.t_max),
+            face: self.face,
+        };
+        self.t = self.side_dist[side];
+        self.side_dist[side] += self.delta_dist[side];
+        self.map[side] += self.step[side];
+        self.face = Some(Face::entered(side, self.step[side]));
+        Some(current)
+    }
+}
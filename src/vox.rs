@@ -0,0 +1,673 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use cgmath::Vector3;
+
+use crate::grid::VoxelGrid;
+use crate::palette::{pack_rgba, Palette};
+
+const MAGIC: [u8; 4] = *b"VOX ";
+const VERSION: i32 = 150;
+
+/// Largest model size MagicaVoxel handles along an axis, bigger grids are split in models.
+pub const MAX_MODEL_SIZE: usize = 256;
+/// Largest grid a file is loaded into, in voxels. Model translations can spread models far
+/// apart, the grid holds the bounds of all of them.
+pub const MAX_VOLUME: usize = 1 << 30;
+
+struct Model {
+    size: [i32; 3],
+    /// x, y, z and color index
+    voxels: Vec<[u8; 4]>,
+}
+
+/// Rotation as a signed permutation matrix followed by a translation, in .vox space.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Transform {
+    rotation: [[i32; 3]; 3],
+    translation: [i32; 3],
+}
+
+impl Transform {
+    const IDENTITY: Transform = Transform {
+        rotation: [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+        translation: [0, 0, 0],
+    };
+    /// None when the result doesn't fit in an i32, translations come straight from the file.
+    fn rotate(&self, v: [i32; 3]) -> Option<[i32; 3]> {
+        let mut rotated = [0i32; 3];
+        for (row, value) in rotated.iter_mut().enumerate() {
+            for (entry, component) in self.rotation[row].iter().zip(v) {
+                *value = value.checked_add(entry.checked_mul(component)?)?;
+            }
+        }
+        Some(rotated)
+    }
+    fn apply(&self, v: [i32; 3]) -> Option<[i32; 3]> {
+        let rotated = self.rotate(v)?;
+        let mut applied = [0; 3];
+        for axis in 0..3 {
+            applied[axis] = rotated[axis].checked_add(self.translation[axis])?;
+        }
+        Some(applied)
+    }
+    /// `self` applied after `child`.
+    fn then(&self, child: &Transform) -> Option<Transform> {
+        let rotation = std::array::from_fn(|row| {
+            std::array::from_fn(|col| {
+                (0..3)
+                    .map(|k| self.rotation[row][k] * child.rotation[k][col])
+                    .sum()
+            })
+        });
+        Some(Transform {
+            rotation,
+            translation: self.apply(child.translation)?,
+        })
+    }
+    /// Decodes the packed `_r` rotation: bits 0-1 and 2-3 are the column of the non zero entry
+    /// in the first and second row, bits 4, 5 and 6 the sign of the first, second and third row.
+    fn rotation_from_byte(byte: u8) -> Option<[[i32; 3]; 3]> {
+        let first = (byte & 3) as usize;
+        let second = (byte >> 2 & 3) as usize;
+        if first > 2 || second > 2 || first == second {
+            return None;
+        }
+        let columns = [first, second, 3 - first - second];
+        let mut rotation = [[0; 3]; 3];
+        for (row, column) in columns.into_iter().enumerate() {
+            rotation[row][column] = if byte & (1 << (4 + row)) != 0 { -1 } else { 1 };
+        }
+        Some(rotation)
+    }
+}
+
+enum Node {
+    Transform {
+        child: i32,
+        transform: Transform,
+        hidden: bool,
+    },
+    Group {
+        children: Vec<i32>,
+    },
+    Shape {
+        models: Vec<i32>,
+    },
+}
+
+/// Bounds checked reader over the bytes of a .vox file.
+struct Chunks<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn out_of_range() -> io::Error {
+    invalid("model translation out of range in .vox file")
+}
+
+impl<'a> Chunks<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+    fn is_empty(&self) -> bool {
+        self.position >= self.data.len()
+    }
+    fn bytes(&mut self, count: usize) -> io::Result<&'a [u8]> {
+        if count > self.data.len() - self.position {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated .vox file",
+            ));
+        }
+        let bytes = &self.data[self.position..self.position + count];
+        self.position += count;
+        Ok(bytes)
+    }
+    fn i32(&mut self) -> io::Result<i32> {
+        Ok(i32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+    fn count(&mut self) -> io::Result<usize> {
+        usize::try_from(self.i32()?).map_err(|_| invalid("negative count in .vox file"))
+    }
+    fn string(&mut self) -> io::Result<String> {
+        let length = self.count()?;
+        Ok(String::from_utf8_lossy(self.bytes(length)?).into_owned())
+    }
+    fn dict(&mut self) -> io::Result<HashMap<String, String>> {
+        let mut dict = HashMap::new();
+        for _ in 0..self.count()? {
+            let key = self.string()?;
+            let value = self.string()?;
+            dict.insert(key, value);
+        }
+        Ok(dict)
+    }
+    /// Next chunk id with its content and children.
+    fn chunk(&mut self) -> io::Result<([u8; 4], Chunks<'a>, Chunks<'a>)> {
+        let id = self.bytes(4)?.try_into().unwrap();
+        let content_size = self.count()?;
+        let children_size = self.count()?;
+        let content = Chunks::new(self.bytes(content_size)?);
+        let children = Chunks::new(self.bytes(children_size)?);
+        Ok((id, content, children))
+    }
+}
+
+/// Default MagicaVoxel palette, used when a file has no RGBA chunk: a 6x6x6 colour cube
+/// without black followed by red, green, blue and grey ramps.
+pub fn default_palette() -> Palette {
+    const CUBE: [u8; 6] = [0xFF, 0xCC, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u8; 10] = [0xEE, 0xDD, 0xBB, 0xAA, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    let mut palette = Palette::new();
+    let mut index = 1;
+    for r in CUBE {
+        for g in CUBE {
+            for b in CUBE {
+                if r != 0 || g != 0 || b != 0 {
+                    palette.set(index, pack_rgba(r, g, b, 0xFF));
+                    index += 1;
+                }
+            }
+        }
+    }
+    for channel in [[1, 0, 0], [0, 1, 0], [0, 0, 1], [1, 1, 1]] {
+        for value in RAMP {
+            let [r, g, b] = channel.map(|c| c * value);
+            palette.set(index, pack_rgba(r, g, b, 0xFF));
+            index = index.wrapping_add(1);
+        }
+    }
+    palette
+}
+
+/// Reads a MagicaVoxel .vox file, see
+/// https://github.com/ephtracy/voxel-model/blob/master/MagicaVoxel-file-format-vox.txt
+///
+/// A file is a "VOX " header and version followed by a MAIN chunk holding all other chunks.
+/// Every chunk is an id, the size of its content, the size of its children, the content and
+/// then the children. All numbers are little endian.
+///
+/// .vox is z up and the grid is y up, a voxel at .vox (x, y, z) ends up at grid (x, z, -y) so
+/// models are not mirrored. All model instances of the scene graph are placed in one grid of
+/// unit voxels at the origin, sized to the bounds of the models. Color index 0 is empty in .vox
+/// as well, so the indices are used as materials directly.
+pub fn read(data: &[u8]) -> io::Result<(VoxelGrid, Palette)> {
+    let mut file = Chunks::new(data);
+    if file.bytes(4)? != MAGIC {
+        return Err(invalid("not a .vox file"));
+    }
+    let _version = file.i32()?;
+    let (id, _, mut chunks) = file.chunk()?;
+    if id != *b"MAIN" {
+        return Err(invalid("missing MAIN chunk in .vox file"));
+    }
+
+    let mut models = Vec::new();
+    let mut size = None;
+    let mut palette = None;
+    let mut nodes = HashMap::new();
+    while !chunks.is_empty() {
+        let (id, mut content, _) = chunks.chunk()?;
+        match &id {
+            b"SIZE" => {
+                let model_size = [content.i32()?, content.i32()?, content.i32()?];
+                if model_size
+                    .iter()
+                    .any(|s| !(1..=MAX_MODEL_SIZE as i32).contains(s))
+                {
+                    return Err(invalid("invalid model size in .vox file"));
+                }
+                size = Some(model_size);
+            }
+            b"XYZI" => {
+                let size = size
+                    .take()
+                    .ok_or_else(|| invalid("XYZI chunk without SIZE"))?;
+                let count = content.count()?;
+                let voxels: Vec<[u8; 4]> = content
+                    .bytes(
+                        count
+                            .checked_mul(4)
+                            .ok_or_else(|| invalid("too many voxels"))?,
+                    )?
+                    .chunks_exact(4)
+                    .map(|voxel| voxel.try_into().unwrap())
+                    .collect();
+                if voxels
+                    .iter()
+                    .any(|voxel| (0..3).any(|axis| voxel[axis] as i32 >= size[axis]))
+                {
+                    return Err(invalid("voxel outside of its model in .vox file"));
+                }
+                models.push(Model { size, voxels });
+            }
+            b"RGBA" => {
+                // color index i is stored at i - 1, the last entry is unused
+                let mut colors = Palette::new();
+                for (index, rgba) in content
+                    .bytes(256 * 4)?
+                    .chunks_exact(4)
+                    .take(255)
+                    .enumerate()
+                {
+                    colors.set(
+                        index as u8 + 1,
+                        pack_rgba(rgba[0], rgba[1], rgba[2], rgba[3]),
+                    );
+                }
+                palette = Some(colors);
+            }
+            b"nTRN" => {
+                let id = content.i32()?;
+                let attributes = content.dict()?;
+                let child = content.i32()?;
+                let _reserved = content.i32()?;
+                let _layer = content.i32()?;
+                // only the first frame of animated transforms is used
+                let mut transform = Transform::IDENTITY;
+                if content.count()? > 0 {
+                    let frame = content.dict()?;
+                    if let Some(rotation) = frame.get("_r") {
+                        let byte = rotation.trim().parse().map_err(|_| invalid("invalid _r"))?;
+                        transform.rotation = Transform::rotation_from_byte(byte)
+                            .ok_or_else(|| invalid("invalid _r"))?;
+                    }
+                    if let Some(translation) = frame.get("_t") {
+                        let values = translation
+                            .split_whitespace()
+                            .map(|v| v.parse().map_err(|_| invalid("invalid _t")))
+                            .collect::<io::Result<Vec<i32>>>()?;
+                        transform.translation =
+                            values.try_into().map_err(|_| invalid("invalid _t"))?;
+                    }
+                }
+                let hidden = attributes.get("_hidden").is_some_and(|v| v == "1");
+                nodes.insert(
+                    id,
+                    Node::Transform {
+                        child,
+                        transform,
+                        hidden,
+                    },
+                );
+            }
+            b"nGRP" => {
+                let id = content.i32()?;
+                let _attributes = content.dict()?;
+                let children = (0..content.count()?)
+                    .map(|_| content.i32())
+                    .collect::<io::Result<Vec<i32>>>()?;
+                nodes.insert(id, Node::Group { children });
+            }
+            b"nSHP" => {
+                let id = content.i32()?;
+                let _attributes = content.dict()?;
+                let mut shape_models = Vec::new();
+                for _ in 0..content.count()? {
+                    shape_models.push(content.i32()?);
+                    let _model_attributes = content.dict()?;
+                }
+                nodes.insert(
+                    id,
+                    Node::Shape {
+                        models: shape_models,
+                    },
+                );
+            }
+            // materials, layers, cameras, notes and unknown chunks
+            _ => {}
+        }
+    }
+
+    // every model instance with its transform, files without a scene graph put all models at
+    // the origin
+    let mut instances = Vec::new();
+    if nodes.is_empty() {
+        instances.extend((0..models.len()).map(|model| (model, Transform::IDENTITY, false)));
+    } else {
+        collect_instances(&nodes, 0, Transform::IDENTITY, 0, &mut instances)?;
+    }
+
+    // .vox position of every voxel and the bounds of all models, models are centered on their
+    // translation
+    let mut voxels = Vec::new();
+    let mut min = [i32::MAX; 3];
+    let mut max = [i32::MIN; 3];
+    for (model, transform, centered) in instances {
+        let model = models
+            .get(model)
+            .ok_or_else(|| invalid("shape refers to a missing model"))?;
+        let center = if centered {
+            model.size.map(|s| s / 2)
+        } else {
+            [0; 3]
+        };
+        let last = std::array::from_fn(|axis| model.size[axis] - 1 - center[axis]);
+        for corner in [center.map(|c| -c), last] {
+            let corner = transform.apply(corner).ok_or_else(out_of_range)?;
+            min = std::array::from_fn(|axis| min[axis].min(corner[axis]));
+            max = std::array::from_fn(|axis| max[axis].max(corner[axis]));
+        }
+        for voxel in &model.voxels {
+            let local = std::array::from_fn(|axis| voxel[axis] as i32 - center[axis]);
+            voxels.push((transform.apply(local).ok_or_else(out_of_range)?, voxel[3]));
+        }
+    }
+
+    let mut size = [0; 3];
+    if min[0] <= max[0] {
+        for axis in 0..3 {
+            size[axis] = max[axis]
+                .checked_sub(min[axis])
+                .and_then(|extent| usize::try_from(extent).ok())
+                .and_then(|extent| extent.checked_add(1))
+                .ok_or_else(out_of_range)?;
+        }
+        let volume = size[0]
+            .checked_mul(size[1])
+            .and_then(|area| area.checked_mul(size[2]));
+        if volume.is_none_or(|volume| volume > MAX_VOLUME) {
+            return Err(invalid("models of .vox file span too big a grid"));
+        }
+    }
+    let mut grid = VoxelGrid::new(size[0], size[2], size[1], Vector3::new(0.0, 0.0, 0.0), 1.0);
+    for (position, color) in voxels {
+        let [x, y, z] = std::array::from_fn(|axis| (position[axis] - min[axis]) as usize);
+        grid.set(x, z, size[1] - 1 - y, color);
+    }
+    Ok((grid, palette.unwrap_or_else(default_palette)))
+}
+
+/// Writes a grid and palette as a .vox file, the inverse of `read`. Grids bigger than
+/// `MAX_MODEL_SIZE` along an axis are split in models of at most that size, each placed by its
+/// own translation node under one group.
+pub fn write(grid: &VoxelGrid, palette: &Palette) -> Vec<u8> {
+    let [size_x, size_y, size_z] = grid.size();
+    // .vox sizes, y up in the grid is z up in .vox
+    let size = [size_x, size_z, size_y];
+    let models_per_axis = size.map(|s| s.div_ceil(MAX_MODEL_SIZE));
+
+    let mut models = Vec::new();
+    let mut shapes = Vec::new();
+    for mz in 0..models_per_axis[2] {
+        for my in 0..models_per_axis[1] {
+            for mx in 0..models_per_axis[0] {
+                let min = [mx, my, mz].map(|m| m * MAX_MODEL_SIZE);
+                let model_size: [usize; 3] =
+                    std::array::from_fn(|axis| (size[axis] - min[axis]).min(MAX_MODEL_SIZE));
+                let mut voxels = Vec::new();
+                for z in 0..model_size[2] {
+                    for y in 0..model_size[1] {
+                        for x in 0..model_size[0] {
+                            let [vx, vy, vz] = [min[0] + x, min[1] + y, min[2] + z];
+                            let color = grid.get(vx, vz, size_z - 1 - vy);
+                            if color != 0 {
+                                voxels.extend_from_slice(&[x as u8, y as u8, z as u8, color]);
+                            }
+                        }
+                    }
+                }
+
+                let mut content = Vec::new();
+                for s in model_size {
+                    content.extend_from_slice(&(s as i32).to_le_bytes());
+                }
+                models.extend(chunk(b"SIZE", &content, &[]));
+                let mut content = ((voxels.len() / 4) as i32).to_le_bytes().to_vec();
+                content.extend(voxels);
+                models.extend(chunk(b"XYZI", &content, &[]));
+
+                // MagicaVoxel centers a model on its translation
+                let translation: [usize; 3] =
+                    std::array::from_fn(|axis| min[axis] + model_size[axis] / 2);
+                shapes.push(translation.map(|t| t.to_string()).join(" "));
+            }
+        }
+    }
+
+    // root transform, a group and a transform with a shape for every model
+    let mut graph = Vec::new();
+    graph.extend(transform_chunk(0, 1, None));
+    let mut content = Vec::new();
+    content.extend_from_slice(&1i32.to_le_bytes());
+    write_dict(&mut content, &[]);
+    content.extend_from_slice(&(shapes.len() as i32).to_le_bytes());
+    for model in 0..shapes.len() {
+        content.extend_from_slice(&(2 + 2 * model as i32).to_le_bytes());
+    }
+    graph.extend(chunk(b"nGRP", &content, &[]));
+    for (model, translation) in shapes.iter().enumerate() {
+        let id = 2 + 2 * model as i32;
+        graph.extend(transform_chunk(id, id + 1, Some(translation)));
+        let mut content = Vec::new();
+        content.extend_from_slice(&(id + 1).to_le_bytes());
+        write_dict(&mut content, &[]);
+        content.extend_from_slice(&1i32.to_le_bytes());
+        content.extend_from_slice(&(model as i32).to_le_bytes());
+        write_dict(&mut content, &[]);
+        graph.extend(chunk(b"nSHP", &content, &[]));
+    }
+
+    // color index i is stored at i - 1, the last entry is unused
+    let mut colors = Vec::with_capacity(256 * 4);
+    for index in 1..=255 {
+        colors.extend_from_slice(&palette.get(index).to_le_bytes());
+    }
+    colors.extend_from_slice(&[0; 4]);
+
+    let mut children = models;
+    children.extend(graph);
+    children.extend(chunk(b"RGBA", &colors, &[]));
+    let mut data = MAGIC.to_vec();
+    data.extend_from_slice(&VERSION.to_le_bytes());
+    data.extend(chunk(b"MAIN", &[], &children));
+    data
+}
+
+fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+    let mut chunk = id.to_vec();
+    chunk.extend_from_slice(&(content.len() as i32).to_le_bytes());
+    chunk.extend_from_slice(&(children.len() as i32).to_le_bytes());
+    chunk.extend_from_slice(content);
+    chunk.extend_from_slice(children);
+    chunk
+}
+
+fn write_dict(out: &mut Vec<u8>, dict: &[(&str, &str)]) {
+    out.extend_from_slice(&(dict.len() as i32).to_le_bytes());
+    for (key, value) in dict {
+        for string in [key, value] {
+            out.extend_from_slice(&(string.len() as i32).to_le_bytes());
+            out.extend_from_slice(string.as_bytes());
+        }
+    }
+}
+
+/// Transform node with a single frame, translated by `translation` when given.
+fn transform_chunk(id: i32, child: i32, translation: Option<&str>) -> Vec<u8> {
+    let mut content = Vec::new();
+    content.extend_from_slice(&id.to_le_bytes());
+    write_dict(&mut content, &[]);
+    // child, reserved, layer and frame count
+    for value in [child, -1, 0, 1] {
+        content.extend_from_slice(&value.to_le_bytes());
+    }
+    match translation {
+        Some(translation) => write_dict(&mut content, &[("_t", translation)]),
+        None => write_dict(&mut content, &[]),
+    }
+    chunk(b"nTRN", &content, &[])
+}
+
+/// Walks the scene graph from `id` and collects (model, transform, centered) instances.
+fn collect_instances(
+    nodes: &HashMap<i32, Node>,
+    id: i32,
+    transform: Transform,
+    depth: usize,
+    instances: &mut Vec<(usize, Transform, bool)>,
+) -> io::Result<()> {
+    if depth > nodes.len() {
+        return Err(invalid("cycle in .vox scene graph"));
+    }
+    match nodes.get(&id) {
+        Some(Node::Transform {
+            child,
+            transform: local,
+            hidden,
+        }) => {
+            if !hidden {
+                let transform = transform.then(local).ok_or_else(out_of_range)?;
+                collect_instances(nodes, *child, transform, depth + 1, instances)?;
+            }
+        }
+        Some(Node::Group { children }) => {
+            for child in children {
+                collect_instances(nodes, *child, transform, depth + 1, instances)?;
+            }
+        }
+        Some(Node::Shape { models }) => {
+            for model in models {
+                let model = usize::try_from(*model).map_err(|_| invalid("negative model index"))?;
+                instances.push((model, transform, true));
+            }
+        }
+        None => return Err(invalid("missing node in .vox scene graph")),
+    }
+    Ok(())
+}
+
+/// Loads a .vox file, see `read`.
+pub fn load(path: &Path) -> io::Result<(VoxelGrid, Palette)> {
+    read(&fs::read(path)?)
+}
+
+/// Saves a grid and palette as a .vox file, see `write`.
+pub fn save(path: &Path, grid: &VoxelGrid, palette: &Palette) -> io::Result<()> {
+    fs::write(path, write(grid, palette))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use rand_pcg::Pcg64Mcg;
+
+    use super::*;
+
+    /// File with one `size` cube model per translation, each full of color 1 and placed by its
+    /// own transform under a group like `write` does.
+    fn scene(size: i32, translations: &[&str]) -> Vec<u8> {
+        let mut children = Vec::new();
+        for _ in translations {
+            let mut content = Vec::new();
+            for _ in 0..3 {
+                content.extend_from_slice(&size.to_le_bytes());
+            }
+            children.extend(chunk(b"SIZE", &content, &[]));
+            let mut content = 1i32.to_le_bytes().to_vec();
+            content.extend_from_slice(&[0, 0, 0, 1]);
+            children.extend(chunk(b"XYZI", &content, &[]));
+        }
+        children.extend(transform_chunk(0, 1, None));
+        let mut content = 1i32.to_le_bytes().to_vec();
+        write_dict(&mut content, &[]);
+        content.extend_from_slice(&(translations.len() as i32).to_le_bytes());
+        for model in 0..translations.len() {
+            content.extend_from_slice(&(2 + 2 * model as i32).to_le_bytes());
+        }
+        children.extend(chunk(b"nGRP", &content, &[]));
+        for (model, translation) in translations.iter().enumerate() {
+            let id = 2 + 2 * model as i32;
+            children.extend(transform_chunk(id, id + 1, Some(translation)));
+            let mut content = (id + 1).to_le_bytes().to_vec();
+            write_dict(&mut content, &[]);
+            content.extend_from_slice(&1i32.to_le_bytes());
+            content.extend_from_slice(&(model as i32).to_le_bytes());
+            write_dict(&mut content, &[]);
+            children.extend(chunk(b"nSHP", &content, &[]));
+        }
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend(chunk(b"MAIN", &[], &children));
+        data
+    }
+
+    fn assert_invalid(data: &[u8]) {
+        let error = read(data).err().expect("file should be rejected");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_invalid_model_sizes() {
+        let (grid, _) = read(&scene(4, &["0 0 0"])).unwrap();
+        assert_eq!(grid.size(), [4, 4, 4]);
+        for size in [0, -4, MAX_MODEL_SIZE as i32 + 1, i32::MAX] {
+            assert_invalid(&scene(size, &["0 0 0"]));
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_range_translations() {
+        // the far corner of the model overflows
+        assert_invalid(&scene(4, &["2147483647 0 0"]));
+        assert_invalid(&scene(4, &["-2147483648 0 0"]));
+        // both fit on their own but the grid around them doesn't
+        assert_invalid(&scene(1, &["-2147483648 0 0", "2147483647 0 0"]));
+        assert_invalid(&scene(1, &["0 0 0", "100000 100000 100000"]));
+    }
+
+    /// Writes a grid with random voxels, the corners set and a random palette, and checks
+    /// that reading it back gives the same grid and palette. Returns the number of models.
+    fn round_trip(size_x: usize, size_y: usize, size_z: usize) -> usize {
+        let mut rng = Pcg64Mcg::new(3);
+        let mut grid = VoxelGrid::new(size_x, size_y, size_z, Vector3::new(0.0, 0.0, 0.0), 1.0);
+        for _ in 0..2000 {
+            let x = rng.gen_range(0..size_x);
+            let y = rng.gen_range(0..size_y);
+            let z = rng.gen_range(0..size_z);
+            grid.set(x, y, z, rng.gen_range(1..=255));
+        }
+        for corner in 0..8 {
+            let x = if corner & 1 == 0 { 0 } else { size_x - 1 };
+            let y = if corner & 2 == 0 { 0 } else { size_y - 1 };
+            let z = if corner & 4 == 0 { 0 } else { size_z - 1 };
+            grid.set(x, y, z, 1 + corner as u8);
+        }
+        let mut palette = Palette::new();
+        for index in 1..=255 {
+            palette.set(index, rng.gen());
+        }
+
+        let data = write(&grid, &palette);
+        let (read_grid, read_palette) = read(&data).unwrap();
+        assert_eq!(read_grid.size(), grid.size());
+        for y in 0..size_y {
+            for z in 0..size_z {
+                for x in 0..size_x {
+                    assert_eq!(read_grid.get(x, y, z), grid.get(x, y, z));
+                }
+            }
+        }
+        assert_eq!(read_palette.colors, palette.colors);
+        data.windows(4).filter(|id| *id == b"SIZE").count()
+    }
+
+    #[test]
+    fn round_trips_a_single_model() {
+        assert_eq!(round_trip(3, 5, 7), 1);
+    }
+
+    #[test]
+    fn round_trips_grids_split_in_models() {
+        // .vox is z up, grid y is the third .vox axis
+        assert_eq!(round_trip(300, 20, 260), 4);
+        assert_eq!(round_trip(257, 257, 1), 4);
+    }
+}
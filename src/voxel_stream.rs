@@ -0,0 +1,182 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use cgmath::Vector3;
+
+use crate::grid::VoxelGrid;
+use crate::morton;
+
+const MAGIC: [u8; 4] = *b"MVXS";
+const VERSION: u32 = 1;
+
+/// Voxel stream file: a header with the world size followed by (Morton code, material) records
+/// sorted by Morton code. Only solid voxels are stored.
+///
+/// | bytes | content                        |
+/// |-------|--------------------------------|
+/// | 4     | magic "MVXS"                   |
+/// | 4     | version                        |
+/// | 12    | size x, y, z as u32            |
+/// | 9 * n | Morton code u64, material u8   |
+///
+/// All numbers are little endian.
+pub struct VoxelStreamReader<R: Read> {
+    reader: R,
+    size: [usize; 3],
+}
+
+impl VoxelStreamReader<BufReader<File>> {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> VoxelStreamReader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; 20];
+        reader.read_exact(&mut header)?;
+        if header[0..4] != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a voxel stream",
+            ));
+        }
+        let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported voxel stream version {}", version),
+            ));
+        }
+        let size = [
+            u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize,
+            u32::from_le_bytes(header[12..16].try_into().unwrap()) as usize,
+            u32::from_le_bytes(header[16..20].try_into().unwrap()) as usize,
+        ];
+        Ok(Self { reader, size })
+    }
+    pub fn size(&self) -> [usize; 3] {
+        self.size
+    }
+}
+
+impl<R: Read> Iterator for VoxelStreamReader<R> {
+    type Item = io::Result<(u64, u8)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record = [0; 9];
+        let mut read = 0;
+        while read < record.len() {
+            match self.reader.read(&mut record[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Some(Err(error)),
+            }
+        }
+        match read {
+            0 => None,
+            9 => Some(Ok((
+                u64::from_le_bytes(record[0..8].try_into().unwrap()),
+                record[8],
+            ))),
+            _ => Some(Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated voxel record",
+            ))),
+        }
+    }
+}
+
+/// Writes a voxel stream, voxels have to be written in Morton order.
+pub struct VoxelStreamWriter<W: Write> {
+    writer: W,
+    size: [usize; 3],
+    last_code: Option<u64>,
+}
+
+impl VoxelStreamWriter<BufWriter<File>> {
+    pub fn create(path: &Path, size: [usize; 3]) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), size)
+    }
+}
+
+impl<W: Write> VoxelStreamWriter<W> {
+    pub fn new(mut writer: W, size: [usize; 3]) -> io::Result<Self> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        for s in size {
+            writer.write_all(&(s as u32).to_le_bytes())?;
+        }
+        Ok(Self {
+            writer,
+            size,
+            last_code: None,
+        })
+    }
+    pub fn write(&mut self, code: u64, material: u8) -> io::Result<()> {
+        if self.last_code.is_some_and(|last| last >= code) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "voxels must be written in increasing Morton order",
+            ));
+        }
+        let [x, y, z] = morton::decode(code);
+        if x as usize >= self.size[0] || y as usize >= self.size[1] || z as usize >= self.size[2] {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "voxel outside of the voxel stream size",
+            ));
+        }
+        self.last_code = Some(code);
+        self.writer.write_all(&code.to_le_bytes())?;
+        self.writer.write_all(&[material])
+    }
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Writes the solid voxels of a grid as a voxel stream.
+pub fn write_grid(path: &Path, grid: &VoxelGrid) -> io::Result<()> {
+    let [size_x, size_y, size_z] = grid.size();
+    let mut voxels = Vec::new();
+    for y in 0..size_y {
+        for z in 0..size_z {
+            for x in 0..size_x {
+                let material = grid.get(x, y, z);
+                if material != 0 {
+                    voxels.push((morton::encode(x as u32, y as u32, z as u32), material));
+                }
+            }
+        }
+    }
+    voxels.sort_unstable_by_key(|(code, _)| *code);
+    let mut writer = VoxelStreamWriter::create(path, grid.size())?;
+    for (code, material) in voxels {
+        writer.write(code, material)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Reads a voxel stream into a grid of unit voxels at the origin.
+pub fn read_grid(path: &Path) -> io::Result<VoxelGrid> {
+    let voxels = VoxelStreamReader::open(path)?;
+    let [size_x, size_y, size_z] = voxels.size();
+    let mut grid = VoxelGrid::new(size_x, size_y, size_z, Vector3::new(0.0, 0.0, 0.0), 1.0);
+    for voxel in voxels {
+        let (code, material) = voxel?;
+        let [x, y, z] = morton::decode(code);
+        if !grid.contains(x as i32, y as i32, z as i32) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "voxel outside of the voxel stream size",
+            ));
+        }
+        grid.set(x as usize, y as usize, z as usize, material);
+    }
+    Ok(grid)
+}
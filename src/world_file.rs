@@ -0,0 +1,540 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use cgmath::Vector3;
+
+use crate::grid::VoxelGrid;
+use crate::palette::Palette;
+
+const MAGIC: [u8; 4] = *b"MVXW";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 4 + 4 + 12 + 24 + 8 + 256 * 4 + 4;
+
+/// Chunks are cubes of this many voxels along every axis, chunks at the far edges of the world
+/// are cut off at the world size.
+pub const CHUNK_SIZE: usize = 32;
+
+const ENCODING_RLE: u8 = 0;
+const ENCODING_PACKED: u8 = 1;
+
+/// Everything that can be wrong with a world file.
+#[derive(Debug)]
+pub enum WorldFileError {
+    Io(io::Error),
+    /// The file does not start with the world file magic.
+    NotAWorldFile,
+    UnsupportedVersion(u32),
+    /// The file ends before all data is read.
+    Truncated,
+    /// The checksum does not match the contents, the file is damaged.
+    ChecksumMismatch,
+    /// The contents are inconsistent.
+    Corrupt(String),
+}
+
+impl fmt::Display for WorldFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WorldFileError::Io(error) => write!(f, "{}", error),
+            WorldFileError::NotAWorldFile => write!(f, "not a world file"),
+            WorldFileError::UnsupportedVersion(version) => {
+                write!(f, "unsupported world file version {}", version)
+            }
+            WorldFileError::Truncated => write!(f, "truncated world file"),
+            WorldFileError::ChecksumMismatch => write!(f, "world file checksum mismatch"),
+            WorldFileError::Corrupt(message) => write!(f, "corrupt world file: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for WorldFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WorldFileError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WorldFileError {
+    fn from(error: io::Error) -> Self {
+        WorldFileError::Io(error)
+    }
+}
+
+fn corrupt(message: &str) -> WorldFileError {
+    WorldFileError::Corrupt(message.to_string())
+}
+
+/// CRC-32 (IEEE) lookup table.
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, byte| {
+        CRC_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Chunk `chunk` of a world of `size`, as the first voxel and the size of the chunk.
+fn chunk_bounds(size: [usize; 3], chunk: [usize; 3]) -> ([usize; 3], [usize; 3]) {
+    let min = chunk.map(|c| c * CHUNK_SIZE);
+    let chunk_size = std::array::from_fn(|axis| (size[axis] - min[axis]).min(CHUNK_SIZE));
+    (min, chunk_size)
+}
+
+/// Chunk coordinates in file order: y, then z, then x like the voxels in a grid.
+fn chunks(size: [usize; 3]) -> impl Iterator<Item = [usize; 3]> {
+    let [chunks_x, chunks_y, chunks_z] = size.map(|s| s.div_ceil(CHUNK_SIZE));
+    (0..chunks_y)
+        .flat_map(move |y| (0..chunks_z).flat_map(move |z| (0..chunks_x).map(move |x| [x, y, z])))
+}
+
+/// Run length encoding as (run length - 1 as u16, material) pairs.
+fn encode_rle(voxels: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    let mut start = 0;
+    while start < voxels.len() {
+        let material = voxels[start];
+        let run = voxels[start..]
+            .iter()
+            .take(1 << 16)
+            .take_while(|v| **v == material)
+            .count();
+        payload.extend_from_slice(&((run - 1) as u16).to_le_bytes());
+        payload.push(material);
+        start += run;
+    }
+    payload
+}
+
+fn decode_rle(payload: &[u8], voxels: &mut Vec<u8>, count: usize) -> Result<(), WorldFileError> {
+    if !payload.len().is_multiple_of(3) {
+        return Err(corrupt("run length payload is not a whole number of runs"));
+    }
+    for run in payload.chunks_exact(3) {
+        let length = u16::from_le_bytes([run[0], run[1]]) as usize + 1;
+        if voxels.len() + length > count {
+            return Err(corrupt("runs overflow their chunk"));
+        }
+        voxels.extend(std::iter::repeat_n(run[2], length));
+    }
+    if voxels.len() != count {
+        return Err(corrupt("runs do not fill their chunk"));
+    }
+    Ok(())
+}
+
+/// Bits per voxel needed to index a chunk palette of `count` materials.
+fn bits_for(count: usize) -> usize {
+    (usize::BITS - (count - 1).leading_zeros()) as usize
+}
+
+/// Palette bit-packing: the number of materials in the chunk minus one as u8, the materials,
+/// then every voxel as an index into these materials with as few bits as possible, least
+/// significant bit first.
+fn encode_packed(voxels: &[u8]) -> Vec<u8> {
+    let mut index = [0u8; 256];
+    let mut materials = Vec::new();
+    for &voxel in voxels {
+        if !materials.contains(&voxel) {
+            index[voxel as usize] = materials.len() as u8;
+            materials.push(voxel);
+        }
+    }
+    let bits = bits_for(materials.len());
+    let mut payload = vec![(materials.len() - 1) as u8];
+    payload.extend_from_slice(&materials);
+    let mut packed = vec![0u8; (voxels.len() * bits).div_ceil(8)];
+    for (i, voxel) in voxels.iter().enumerate() {
+        let value = index[*voxel as usize] as usize;
+        for bit in 0..bits {
+            if value & (1 << bit) != 0 {
+                let position = i * bits + bit;
+                packed[position / 8] |= 1 << (position % 8);
+            }
+        }
+    }
+    payload.extend(packed);
+    payload
+}
+
+fn decode_packed(payload: &[u8], voxels: &mut Vec<u8>, count: usize) -> Result<(), WorldFileError> {
+    let material_count = *payload
+        .first()
+        .ok_or_else(|| corrupt("empty packed payload"))? as usize
+        + 1;
+    let materials = payload
+        .get(1..1 + material_count)
+        .ok_or_else(|| corrupt("packed payload shorter than its materials"))?;
+    let packed = &payload[1 + material_count..];
+    let bits = bits_for(material_count);
+    if packed.len() != (count * bits).div_ceil(8) {
+        return Err(corrupt("packed payload does not match its chunk size"));
+    }
+    for i in 0..count {
+        let mut value = 0;
+        for bit in 0..bits {
+            let position = i * bits + bit;
+            if packed[position / 8] & (1 << (position % 8)) != 0 {
+                value |= 1 << bit;
+            }
+        }
+        voxels.push(
+            *materials
+                .get(value)
+                .ok_or_else(|| corrupt("packed index outside of the chunk materials"))?,
+        );
+    }
+    Ok(())
+}
+
+/// Writes a grid and its palette in the native world format:
+///
+/// | bytes     | content                                      |
+/// |-----------|----------------------------------------------|
+/// | 4         | magic "MVXW"                                 |
+/// | 4         | version                                      |
+/// | 12        | size x, y, z as u32                          |
+/// | 24        | origin x, y, z as f64                        |
+/// | 8         | voxel size as f64                            |
+/// | 1024      | palette, 256 colors as u32 0xAABBGGRR        |
+/// | 4         | chunk count                                  |
+/// | per chunk | encoding u8, payload size u32, payload       |
+/// | 4         | CRC-32 of everything before it               |
+///
+/// All numbers are little endian. The world is split in chunks of `CHUNK_SIZE` voxels, ordered
+/// and filled like the grid. Every chunk is stored run length encoded or palette bit-packed,
+/// whichever is smaller.
+pub fn write(grid: &VoxelGrid, palette: &Palette) -> Vec<u8> {
+    let size = grid.size();
+    let mut data = Vec::with_capacity(HEADER_SIZE);
+    data.extend_from_slice(&MAGIC);
+    data.extend_from_slice(&VERSION.to_le_bytes());
+    for s in size {
+        data.extend_from_slice(&(s as u32).to_le_bytes());
+    }
+    let origin = grid.origin();
+    for o in [origin.x, origin.y, origin.z, grid.voxel_size()] {
+        data.extend_from_slice(&o.to_le_bytes());
+    }
+    for color in palette.colors {
+        data.extend_from_slice(&color.to_le_bytes());
+    }
+    data.extend_from_slice(&(chunks(size).count() as u32).to_le_bytes());
+
+    let mut voxels = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
+    for chunk in chunks(size) {
+        let (min, chunk_size) = chunk_bounds(size, chunk);
+        voxels.clear();
+        for y in min[1]..min[1] + chunk_size[1] {
+            for z in min[2]..min[2] + chunk_size[2] {
+                for x in min[0]..min[0] + chunk_size[0] {
+                    voxels.push(grid.get(x, y, z));
+                }
+            }
+        }
+        let rle = encode_rle(&voxels);
+        let packed = encode_packed(&voxels);
+        let (encoding, payload) = if rle.len() <= packed.len() {
+            (ENCODING_RLE, rle)
+        } else {
+            (ENCODING_PACKED, packed)
+        };
+        data.push(encoding);
+        data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        data.extend(payload);
+    }
+    let checksum = crc32(&data);
+    data.extend_from_slice(&checksum.to_le_bytes());
+    data
+}
+
+/// Reads a world file written by `write`, everything is validated before it is used.
+pub fn read(data: &[u8]) -> Result<(VoxelGrid, Palette), WorldFileError> {
+    if data.len() < MAGIC.len() || data[0..4] != MAGIC {
+        return Err(WorldFileError::NotAWorldFile);
+    }
+    if data.len() < 8 {
+        return Err(WorldFileError::Truncated);
+    }
+    let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    let read_f64 = |offset: usize| f64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    let version = read_u32(4);
+    if version != VERSION {
+        return Err(WorldFileError::UnsupportedVersion(version));
+    }
+    if data.len() < HEADER_SIZE + 4 {
+        return Err(WorldFileError::Truncated);
+    }
+
+    let size = [8, 12, 16].map(|offset| read_u32(offset) as usize);
+    if size
+        .iter()
+        .try_fold(1usize, |product, s| product.checked_mul(*s))
+        .is_none()
+    {
+        return Err(corrupt("world size overflows"));
+    }
+    let origin = Vector3::new(read_f64(20), read_f64(28), read_f64(36));
+    let voxel_size = read_f64(44);
+    if !(voxel_size.is_finite() && voxel_size > 0.0) {
+        return Err(corrupt("voxel size is not a positive number"));
+    }
+    let mut palette = Palette::new();
+    for index in 0..256 {
+        palette.colors[index] = read_u32(52 + index * 4);
+    }
+    let chunk_count = read_u32(HEADER_SIZE - 4) as usize;
+    if chunk_count != size.map(|s| s.div_ceil(CHUNK_SIZE)).iter().product() {
+        return Err(corrupt("chunk count does not match the world size"));
+    }
+
+    // walk the chunk sizes first so a truncated file is reported as such and not as a checksum
+    // mismatch, chunks are only decoded once the checksum shows they are intact
+    let mut offset = HEADER_SIZE;
+    for _ in 0..chunk_count {
+        if data.len() < offset + 5 {
+            return Err(WorldFileError::Truncated);
+        }
+        offset += 5 + read_u32(offset + 1) as usize;
+    }
+    if data.len() < offset + 4 {
+        return Err(WorldFileError::Truncated);
+    }
+    if data.len() > offset + 4 {
+        return Err(corrupt("data after the end of the world"));
+    }
+    if crc32(&data[..offset]) != read_u32(offset) {
+        return Err(WorldFileError::ChecksumMismatch);
+    }
+
+    let mut grid = VoxelGrid::new(size[0], size[1], size[2], origin, voxel_size);
+    let mut voxels = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
+    let mut offset = HEADER_SIZE;
+    for chunk in chunks(size) {
+        let (min, chunk_size) = chunk_bounds(size, chunk);
+        let encoding = data[offset];
+        let payload_size = read_u32(offset + 1) as usize;
+        let payload = &data[offset + 5..offset + 5 + payload_size];
+        offset += 5 + payload_size;
+
+        let count = chunk_size.iter().product();
+        voxels.clear();
+        match encoding {
+            ENCODING_RLE => decode_rle(payload, &mut voxels, count)?,
+            ENCODING_PACKED => decode_packed(payload, &mut voxels, count)?,
+            _ => return Err(corrupt("unknown chunk encoding")),
+        }
+        let mut voxels = voxels.iter();
+        for y in min[1]..min[1] + chunk_size[1] {
+            for z in min[2]..min[2] + chunk_size[2] {
+                for x in min[0]..min[0] + chunk_size[0] {
+                    grid.set(x, y, z, *voxels.next().unwrap());
+                }
+            }
+        }
+    }
+    Ok((grid, palette))
+}
+
+/// Loads a world file, see `read`.
+pub fn load(path: &Path) -> Result<(VoxelGrid, Palette), WorldFileError> {
+    read(&fs::read(path)?)
+}
+
+/// Saves a grid and palette as a world file, see `write`.
+pub fn save(path: &Path, grid: &VoxelGrid, palette: &Palette) -> io::Result<()> {
+    fs::write(path, write(grid, palette))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two chunks along x, y and z with the far ones cut off. The bottom is layers of two
+    /// materials that run length encode well and the rest is a noisy mix of three materials
+    /// that packs better.
+    fn world() -> (VoxelGrid, Palette) {
+        let mut grid = VoxelGrid::new(40, 50, 35, Vector3::new(-1.5, 2.0, 0.25), 0.5);
+        for y in 0..50 {
+            for z in 0..35 {
+                for x in 0..40 {
+                    let material = if y < 32 {
+                        7 + (y % 2) as u8
+                    } else {
+                        [0, 3, 9][(x * 7 + y * 13 + z * 5) % 3]
+                    };
+                    grid.set(x, y, z, material);
+                }
+            }
+        }
+        let mut palette = Palette::new();
+        for index in 0..=255 {
+            palette.set(index, 0x01020304u32.wrapping_mul(index as u32));
+        }
+        (grid, palette)
+    }
+
+    /// Encoding of every chunk of a file.
+    fn encodings(data: &[u8]) -> Vec<u8> {
+        let chunk_count =
+            u32::from_le_bytes(data[HEADER_SIZE - 4..HEADER_SIZE].try_into().unwrap());
+        let mut offset = HEADER_SIZE;
+        let mut encodings = Vec::new();
+        for _ in 0..chunk_count {
+            encodings.push(data[offset]);
+            offset +=
+                5 + u32::from_le_bytes(data[offset + 1..offset + 5].try_into().unwrap()) as usize;
+        }
+        encodings
+    }
+
+    /// Recomputes the checksum after the contents were changed.
+    fn reseal(data: &mut [u8]) {
+        let end = data.len() - 4;
+        let checksum = crc32(&data[..end]);
+        data[end..].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    #[test]
+    fn round_trip() {
+        let (grid, palette) = world();
+        let data = write(&grid, &palette);
+        let encodings = encodings(&data);
+        assert_eq!(encodings.len(), 8);
+        assert!(encodings.contains(&ENCODING_RLE));
+        assert!(encodings.contains(&ENCODING_PACKED));
+
+        let (read_grid, read_palette) = read(&data).unwrap();
+        assert_eq!(read_grid.size(), grid.size());
+        assert_eq!(read_grid.origin(), grid.origin());
+        assert_eq!(read_grid.voxel_size(), grid.voxel_size());
+        for y in 0..50 {
+            for z in 0..35 {
+                for x in 0..40 {
+                    assert_eq!(read_grid.get(x, y, z), grid.get(x, y, z));
+                }
+            }
+        }
+        assert_eq!(read_palette.colors, palette.colors);
+    }
+
+    #[test]
+    fn round_trip_empty_world() {
+        let grid = VoxelGrid::new(0, 0, 0, Vector3::new(0.0, 0.0, 0.0), 1.0);
+        let (read_grid, _) = read(&write(&grid, &Palette::new())).unwrap();
+        assert_eq!(read_grid.size(), [0; 3]);
+    }
+
+    #[test]
+    fn not_a_world_file() {
+        assert!(matches!(read(b""), Err(WorldFileError::NotAWorldFile)));
+        assert!(matches!(
+            read(b"VOX \x96\0\0\0"),
+            Err(WorldFileError::NotAWorldFile)
+        ));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let (grid, palette) = world();
+        let mut data = write(&grid, &palette);
+        data[4..8].copy_from_slice(&2u32.to_le_bytes());
+        assert!(matches!(
+            read(&data),
+            Err(WorldFileError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn truncated() {
+        let (grid, palette) = world();
+        let data = write(&grid, &palette);
+        for length in [
+            6,
+            8,
+            HEADER_SIZE,
+            HEADER_SIZE + 3,
+            data.len() - 40,
+            data.len() - 1,
+        ] {
+            assert!(
+                matches!(read(&data[..length]), Err(WorldFileError::Truncated)),
+                "length {}",
+                length
+            );
+        }
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let (grid, palette) = world();
+        let data = write(&grid, &palette);
+        // a palette color, a voxel payload byte and the checksum itself
+        for offset in [100, data.len() - 10, data.len() - 1] {
+            let mut damaged = data.clone();
+            damaged[offset] ^= 0x10;
+            assert!(
+                matches!(read(&damaged), Err(WorldFileError::ChecksumMismatch)),
+                "offset {}",
+                offset
+            );
+        }
+    }
+
+    #[test]
+    fn corrupt() {
+        let (grid, palette) = world();
+        let data = write(&grid, &palette);
+
+        let mut wrong_chunk_count = data.clone();
+        wrong_chunk_count[HEADER_SIZE - 4..HEADER_SIZE].copy_from_slice(&9u32.to_le_bytes());
+        let mut zero_voxel_size = data.clone();
+        zero_voxel_size[44..52].copy_from_slice(&0.0f64.to_le_bytes());
+        let mut trailing_data = data.clone();
+        trailing_data.insert(data.len() - 4, 0);
+        let mut unknown_encoding = data.clone();
+        unknown_encoding[HEADER_SIZE] = 7;
+        reseal(&mut unknown_encoding);
+        // the first chunk is layers, the first run is one voxel too short
+        assert_eq!(encodings(&data)[0], ENCODING_RLE);
+        let mut short_run = data.clone();
+        let run = u16::from_le_bytes([data[HEADER_SIZE + 5], data[HEADER_SIZE + 6]]);
+        short_run[HEADER_SIZE + 5..HEADER_SIZE + 7].copy_from_slice(&(run - 1).to_le_bytes());
+        reseal(&mut short_run);
+
+        for (name, data) in [
+            ("chunk count", wrong_chunk_count),
+            ("voxel size", zero_voxel_size),
+            ("trailing data", trailing_data),
+            ("unknown encoding", unknown_encoding),
+            ("short run", short_run),
+        ] {
+            assert!(
+                matches!(read(&data), Err(WorldFileError::Corrupt(_))),
+                "{}",
+                name
+            );
+        }
+    }
+}